@@ -0,0 +1,2 @@
+-- Limits scoped to protected channels and roles
+ALTER TABLE limits ADD COLUMN IF NOT EXISTS limit_targets TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,5 @@
+-- Channel raid alerts and other logs are posted to
+ALTER TABLE guilds ADD COLUMN IF NOT EXISTS log_channel TEXT;
+
+-- Joins are counted per guild on every join
+CREATE INDEX IF NOT EXISTS member_joins_guild_id_joined_at ON member_joins (guild_id, joined_at);
@@ -0,0 +1,8 @@
+-- Role given to members that should be quarantined
+ALTER TABLE guilds ADD COLUMN IF NOT EXISTS quarantine_role TEXT;
+
+-- Keep the history of join actions when their rule is removed
+ALTER TABLE join_actions ALTER COLUMN rule_id DROP NOT NULL;
+ALTER TABLE join_actions DROP CONSTRAINT IF EXISTS join_actions_rule_id_fkey;
+ALTER TABLE join_actions ADD CONSTRAINT join_actions_rule_id_fkey
+    FOREIGN KEY (rule_id) REFERENCES join_rules(rule_id) ON DELETE SET NULL ON UPDATE CASCADE;
@@ -0,0 +1,2 @@
+-- Whether invites were paused by the lockdown and should be resumed once it ends
+ALTER TABLE lockdowns ADD COLUMN IF NOT EXISTS invites_paused BOOLEAN NOT NULL DEFAULT false;
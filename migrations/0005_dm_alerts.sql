@@ -0,0 +1,2 @@
+-- Minimum severity the owner and admins are sent DMs for, DMs are disabled if NULL
+ALTER TABLE guilds ADD COLUMN IF NOT EXISTS dm_severity TEXT;
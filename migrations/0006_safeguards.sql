@@ -0,0 +1,4 @@
+-- How changes weakening protection are held back, see core::SafeguardMode. Disabled if NULL
+ALTER TABLE guilds ADD COLUMN IF NOT EXISTS safeguard_mode TEXT;
+-- How long weakening changes must wait in the cooldown safeguard mode
+ALTER TABLE guilds ADD COLUMN IF NOT EXISTS safeguard_cooldown INTERVAL NOT NULL DEFAULT '1 hour';
@@ -0,0 +1,2 @@
+-- Whether the punishment for a limit hit could not be carried out
+ALTER TABLE past_hit_limits ADD COLUMN IF NOT EXISTS punishment_failed BOOLEAN NOT NULL DEFAULT FALSE;
@@ -1,29 +1,74 @@
 CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
 
-CREATE TABLE guilds (
-    guild_id TEXT PRIMARY KEY
+CREATE TABLE IF NOT EXISTS guilds (
+    guild_id TEXT PRIMARY KEY,
+    -- Channel SkyNet posts raid alerts and other logs to
+    log_channel TEXT,
+    -- Role given to members that should be quarantined
+    quarantine_role TEXT,
+    -- Minimum severity the owner and admins are sent DMs for, DMs are disabled if NULL
+    dm_severity TEXT,
+    -- How changes weakening protection are held back, see core::SafeguardMode. Disabled if NULL
+    safeguard_mode TEXT,
+    -- How long weakening changes must wait in the cooldown safeguard mode
+    safeguard_cooldown INTERVAL NOT NULL DEFAULT '1 hour'
 );
 
-CREATE TABLE guild_admins (
+CREATE TABLE IF NOT EXISTS guild_admins (
     guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
     user_id TEXT NOT NULL
 );
 
+-- Members with any of these roles are guild admins
+CREATE TABLE IF NOT EXISTS guild_admin_roles (
+    guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    role_id TEXT NOT NULL,
+    PRIMARY KEY (guild_id, role_id)
+);
+
+-- Capabilities granted to users or roles, see core::Capability
+CREATE TABLE IF NOT EXISTS guild_capabilities (
+    guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    target_id TEXT NOT NULL,
+    target_type TEXT NOT NULL, -- user or role
+    capability TEXT NOT NULL,
+    PRIMARY KEY (guild_id, target_id, capability)
+);
+
+-- Users and roles whose actions are never counted towards limits
+CREATE TABLE IF NOT EXISTS guild_whitelist (
+    guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    target_id TEXT NOT NULL,
+    target_type TEXT NOT NULL, -- user or role
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (guild_id, target_id)
+);
+
 -- Stores the limits that are applied to a guild
-CREATE TABLE limits (
+CREATE TABLE IF NOT EXISTS limits (
     guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
     limit_id TEXT PRIMARY KEY DEFAULT uuid_generate_v4(),
     limit_name TEXT NOT NULL default 'Untitled',
     limit_type TEXT NOT NULL,
     limit_action TEXT NOT NULL,
     limit_per INTEGER NOT NULL,
-    limit_time INTERVAL NOT NULL
+    limit_time INTERVAL NOT NULL,
+    -- If not empty, only actions on these channels/roles count towards the limit
+    limit_targets TEXT[] NOT NULL DEFAULT '{}'
 );
 
+-- Stores the weight each action type contributes towards threat score limits
+-- Action types without a row here have a weight of 1
+CREATE TABLE IF NOT EXISTS limit_weights (
+    guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    limit_type TEXT NOT NULL,
+    weight INTEGER NOT NULL DEFAULT 1,
+    PRIMARY KEY (guild_id, limit_type)
+);
 
 -- Stores a list of user actions and which limits they have hit
 -- A user action contributes to a limit
-CREATE TABLE user_actions (
+CREATE TABLE IF NOT EXISTS user_actions (
     action_id TEXT PRIMARY KEY,
     limit_type TEXT NOT NULL,
     created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
@@ -34,12 +79,111 @@ CREATE TABLE user_actions (
 );
 
 -- Stores the past limits that have been applied in a guild
-CREATE TABLE past_hit_limits (
+CREATE TABLE IF NOT EXISTS past_hit_limits (
     id TEXT PRIMARY KEY,
     user_id TEXT NOT NULL,
     guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
     limit_id TEXT NOT NULL REFERENCES limits(limit_id) ON DELETE CASCADE ON UPDATE CASCADE,
     cause TEXT[] NOT NULL DEFAULT '{}',
     notes TEXT[] NOT NULL DEFAULT '{}',
+    punishment_failed BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Stores the raid detection settings of a guild, raid detection is disabled if no row exists
+CREATE TABLE IF NOT EXISTS raid_config (
+    guild_id TEXT PRIMARY KEY REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    raid_joins INTEGER NOT NULL,
+    raid_time INTERVAL NOT NULL,
+    raid_action TEXT NOT NULL,
+    raise_verification BOOLEAN NOT NULL DEFAULT false,
+    lockdown BOOLEAN NOT NULL DEFAULT false
+);
+
+-- Stores recent member joins for raid detection
+CREATE TABLE IF NOT EXISTS member_joins (
+    guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    user_id TEXT NOT NULL,
+    joined_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Whether the join has already been handled as part of a raid
+    handled BOOLEAN NOT NULL DEFAULT false
+);
+
+CREATE INDEX IF NOT EXISTS member_joins_guild_id_joined_at ON member_joins (guild_id, joined_at);
+
+-- Stores the guilds currently in lockdown and the permissions to restore once it ends
+CREATE TABLE IF NOT EXISTS lockdowns (
+    guild_id TEXT PRIMARY KEY REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    everyone_permissions BIGINT NOT NULL,
+    -- Whether invites were paused by the lockdown and should be resumed once it ends
+    invites_paused BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Stores the rules members are checked against when they join a guild
+-- Every condition that is set must match for the rule to apply
+CREATE TABLE IF NOT EXISTS join_rules (
+    guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    rule_id TEXT PRIMARY KEY DEFAULT uuid_generate_v4(),
+    rule_name TEXT NOT NULL DEFAULT 'Untitled',
+    rule_action TEXT NOT NULL,
+    min_account_age INTERVAL,
+    default_avatar BOOLEAN NOT NULL DEFAULT false,
+    username_pattern TEXT
+);
+
+-- Stores the actions taken on members that matched a join rule
+-- The history is kept when a rule is removed, its rule_id is then NULL
+CREATE TABLE IF NOT EXISTS join_actions (
+    action_id TEXT PRIMARY KEY,
+    guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    user_id TEXT NOT NULL,
+    rule_id TEXT REFERENCES join_rules(rule_id) ON DELETE SET NULL ON UPDATE CASCADE,
+    rule_action TEXT NOT NULL,
+    notes TEXT[] NOT NULL DEFAULT '{}',
     created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-);
\ No newline at end of file
+);
+
+-- Stores the spam detection settings of a guild, spam detection is disabled if no row exists
+-- Thresholds of 0 disable the corresponding check
+CREATE TABLE IF NOT EXISTS spam_config (
+    guild_id TEXT PRIMARY KEY REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    spam_time INTERVAL NOT NULL,
+    -- Amount of identical messages a user can send within the interval
+    spam_duplicates INTEGER NOT NULL DEFAULT 0,
+    -- Amount of channels a user can post the same message to within the interval
+    spam_channels INTEGER NOT NULL DEFAULT 0,
+    -- Amount of mentions a single message can contain
+    spam_mentions INTEGER NOT NULL DEFAULT 0,
+    spam_invites BOOLEAN NOT NULL DEFAULT false
+);
+
+-- Stores the URLs limit hits and punishments are posted to for external alerting
+CREATE TABLE IF NOT EXISTS guild_webhooks (
+    webhook_id TEXT PRIMARY KEY DEFAULT uuid_generate_v4(),
+    guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    url TEXT NOT NULL,
+    -- Used to sign the body of every request with HMAC-SHA256
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Who changed what in a guilds Skynet configuration
+CREATE TABLE IF NOT EXISTS config_audit (
+    audit_id TEXT PRIMARY KEY DEFAULT uuid_generate_v4(),
+    guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    user_id TEXT NOT NULL,
+    command TEXT NOT NULL,
+    before JSONB,
+    after JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Weakening config changes waiting out the safeguard cooldown
+CREATE TABLE IF NOT EXISTS safeguard_requests (
+    guild_id TEXT NOT NULL REFERENCES guilds(guild_id) ON DELETE CASCADE ON UPDATE CASCADE,
+    user_id TEXT NOT NULL,
+    change TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (guild_id, user_id, change)
+);
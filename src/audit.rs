@@ -0,0 +1,165 @@
+use log::warn;
+use poise::serenity_prelude::{CreateEmbed, User};
+use poise::CreateReply;
+use serde_json::Value;
+use sqlx::PgConnection;
+
+type Error = crate::Error;
+type Context<'a> = crate::Context<'a>;
+
+/// Characters the fields of an embed may use, leaving room for the title and description
+/// within the 6000 characters Discord allows in total
+const MAX_EMBED_FIELDS_LEN: usize = 5500;
+
+/// Formats a before/after value as a JSON code block, truncated to max_len characters
+/// as embed field values are limited to 1024 characters
+fn format_value(value: &Option<Value>, max_len: usize) -> String {
+    let Some(value) = value else {
+        return "None".to_string();
+    };
+
+    let mut json = serde_json::to_string_pretty(value).unwrap_or_default();
+
+    if json.chars().count() > max_len {
+        json = json.chars().take(max_len).collect::<String>() + "...";
+    }
+
+    format!("```json\n{}\n```", json)
+}
+
+/// A recorded configuration change that has not been posted to the log channel yet
+#[must_use = "the change is only posted to the log channel by calling post"]
+pub struct AuditLog {
+    embed: CreateEmbed<'static>,
+}
+
+impl AuditLog {
+    /// Posts the change to the log channel, once the transaction recording it is committed
+    pub async fn post(self, ctx: Context<'_>) {
+        let Some(guild_id) = ctx.guild_id() else {
+            return;
+        };
+
+        // The change has already been made, so a missing or broken log channel is not an error
+        if let Err(e) = crate::utils::send_log(
+            ctx.serenity_context(),
+            &ctx.data().pool,
+            guild_id,
+            self.embed,
+        )
+        .await
+        {
+            warn!("Could not post config change to log channel: {}", e);
+        }
+    }
+}
+
+/// Records a configuration change made by the current command in the transaction making the change
+pub async fn record(
+    ctx: Context<'_>,
+    conn: &mut PgConnection,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<AuditLog, Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+    let command = ctx.command().qualified_name.to_string();
+
+    sqlx::query!(
+        "
+            INSERT INTO config_audit (guild_id, user_id, command, before, after)
+            VALUES ($1, $2, $3, $4, $5)
+        ",
+        guild_id.to_string(),
+        ctx.author().id.to_string(),
+        command,
+        before,
+        after
+    )
+    .execute(conn)
+    .await?;
+
+    let embed = CreateEmbed::new()
+        .title("Configuration Changed")
+        .field("Changed By", format!("<@{}>", ctx.author().id), true)
+        .field("Command", format!("``/{}``", command), true)
+        .field("Before", format_value(&before, 900), false)
+        .field("After", format_value(&after, 900), false)
+        .color(0x00ff00);
+
+    Ok(AuditLog { embed })
+}
+
+/// Configuration audit trail
+#[poise::command(prefix_command, slash_command, guild_only, subcommands("audit_view"))]
+pub async fn audit(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// View recent configuration changes
+#[poise::command(prefix_command, slash_command, guild_only, rename = "view")]
+pub async fn audit_view(
+    ctx: Context<'_>,
+    #[description = "Only show changes made by this user"] user: Option<User>,
+) -> Result<(), Error> {
+    let entries = sqlx::query!(
+        "
+            SELECT user_id, command, before, after, created_at
+            FROM config_audit
+            WHERE guild_id = $1
+            AND ($2::text IS NULL OR user_id = $2)
+            ORDER BY created_at DESC
+            LIMIT 10
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        user.map(|u| u.id.to_string())
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    if entries.is_empty() {
+        ctx.say("No configuration changes have been recorded")
+            .await?;
+        return Ok(());
+    }
+
+    let mut fields = Vec::new();
+    let mut total_len = 0;
+
+    for entry in entries {
+        let name = format!("/{}", entry.command);
+
+        // Both values share a single field here
+        let value = format!(
+            "<@{}> <t:{}:R>\n**Before:** {}**After:** {}",
+            entry.user_id,
+            entry.created_at.timestamp(),
+            format_value(&entry.before, 400),
+            format_value(&entry.after, 400)
+        );
+
+        // Leave the rest of the changes out instead of exceeding the total size of an embed
+        total_len += name.chars().count() + value.chars().count();
+
+        if total_len > MAX_EMBED_FIELDS_LEN {
+            break;
+        }
+
+        fields.push((name, value));
+    }
+
+    let mut embed = CreateEmbed::new()
+        .title("Configuration Changes")
+        .description(format!(
+            "The {} most recent changes, newest first",
+            fields.len()
+        ))
+        .color(0x00ff00);
+
+    for (name, value) in fields {
+        embed = embed.field(name, value, false);
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
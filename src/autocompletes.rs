@@ -31,3 +31,65 @@ pub async fn limits_autocomplete<'a>(
 
     Vec::new()
 }
+
+pub async fn targets_autocomplete<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> Vec<serenity::all::AutocompleteChoice<'a>> {
+    // Offer the channels and roles of the guild as protected targets
+    let Some(guild) = ctx.guild() else {
+        return Vec::new();
+    };
+
+    let partial = partial.to_lowercase();
+
+    let mut choices = Vec::new();
+
+    for channel in guild.channels.values() {
+        if channel.name.to_lowercase().contains(&partial) {
+            choices.push(serenity::all::AutocompleteChoice::new(
+                format!("#{}", channel.name),
+                channel.id.to_string(),
+            ));
+        }
+    }
+
+    for role in guild.roles.values() {
+        if role.name.to_lowercase().contains(&partial) {
+            choices.push(serenity::all::AutocompleteChoice::new(
+                format!("@{}", role.name),
+                role.id.to_string(),
+            ));
+        }
+    }
+
+    // Discord only allows 25 choices
+    choices.truncate(25);
+
+    choices
+}
+
+pub async fn join_rules_autocomplete<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> Vec<serenity::all::AutocompleteChoice<'a>> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Vec::new();
+    };
+
+    let rules = crate::core::JoinRule::from_guild(&ctx.data().pool, guild_id).await;
+
+    if let Ok(rules) = rules {
+        let mut choices = Vec::new();
+
+        for rule in rules {
+            if rule.rule_name.starts_with(partial) {
+                choices.push(serenity::all::AutocompleteChoice::new(rule.rule_name, rule.rule_id));
+            }
+        }
+
+        return choices;
+    }
+
+    Vec::new()
+}
@@ -0,0 +1,251 @@
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+
+type Error = crate::Error;
+
+const WIDTH: usize = 560;
+const HEIGHT: usize = 240;
+const MARGIN: usize = 16;
+
+const BACKGROUND: [u8; 3] = [0x2b, 0x2d, 0x31];
+const GRID: [u8; 3] = [0x3f, 0x41, 0x47];
+
+/// A bar for each value of a series, drawn next to the bars of the other series
+pub struct Series<'a> {
+    pub values: &'a [i64],
+    pub color: [u8; 3],
+}
+
+/// An RGB image being drawn on
+struct Canvas {
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new() -> Self {
+        Self {
+            pixels: BACKGROUND.repeat(WIDTH * HEIGHT),
+        }
+    }
+
+    /// Fills a rectangle, clipping it to the canvas
+    fn fill(&mut self, x: usize, y: usize, w: usize, h: usize, color: [u8; 3]) {
+        for row in y.min(HEIGHT)..(y + h).min(HEIGHT) {
+            for col in x.min(WIDTH)..(x + w).min(WIDTH) {
+                let i = (row * WIDTH + col) * 3;
+                self.pixels[i..i + 3].copy_from_slice(&color);
+            }
+        }
+    }
+
+    /// Encodes the canvas as a PNG
+    fn to_png(&self) -> Result<Vec<u8>, Error> {
+        // Every scanline starts with its filter type, 0 being no filter
+        let mut raw = Vec::with_capacity((WIDTH * 3 + 1) * HEIGHT);
+
+        for row in self.pixels.chunks(WIDTH * 3) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&raw)?;
+        let data = encoder.finish()?;
+
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&(WIDTH as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(HEIGHT as u32).to_be_bytes());
+        // 8 bit depth, truecolor, default compression, filter and no interlacing
+        ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+        write_chunk(&mut png, b"IHDR", &ihdr);
+        write_chunk(&mut png, b"IDAT", &data);
+        write_chunk(&mut png, b"IEND", &[]);
+
+        Ok(png)
+    }
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+/// Renders a grouped bar chart as a PNG, one group per index of the series
+///
+/// There is no text on the chart, labels and a legend are expected to be given alongside it
+pub fn bar_chart(series: &[Series<'_>]) -> Result<Vec<u8>, Error> {
+    let mut canvas = Canvas::new();
+
+    let groups = series.iter().map(|s| s.values.len()).max().unwrap_or(0);
+    let max = series
+        .iter()
+        .flat_map(|s| s.values.iter().copied())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let plot_height = HEIGHT - 2 * MARGIN;
+    let plot_width = WIDTH - 2 * MARGIN;
+
+    // Quarter grid lines, with the bottom one doubling as the axis
+    for i in 0..=4 {
+        canvas.fill(MARGIN, MARGIN + plot_height * i / 4, plot_width, 1, GRID);
+    }
+
+    if groups == 0 || series.is_empty() {
+        return canvas.to_png();
+    }
+
+    let group_width = plot_width / groups;
+    // With more bars than pixels the bars overlap instead of being padded
+    let bar_width = (group_width * 3 / 4 / series.len()).max(1);
+    let padding = group_width.saturating_sub(bar_width * series.len()) / 2;
+
+    for group in 0..groups {
+        for (i, s) in series.iter().enumerate() {
+            let value = s.values.get(group).copied().unwrap_or(0).max(0);
+            let height = (value as usize * plot_height) / max as usize;
+
+            canvas.fill(
+                MARGIN + group * group_width + padding + i * bar_width,
+                MARGIN + plot_height - height,
+                bar_width,
+                height,
+                s.color,
+            );
+        }
+    }
+
+    canvas.to_png()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+
+    use super::*;
+
+    /// Checks the structure of a PNG and returns its decoded scanlines
+    fn decode(png: &[u8]) -> Vec<u8> {
+        assert_eq!(
+            &png[..8],
+            &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']
+        );
+
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let kind = &rest[4..8];
+            let data = &rest[8..8 + len];
+            let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(kind);
+            hasher.update(data);
+            assert_eq!(hasher.finalize(), crc, "bad crc");
+
+            chunks.push((kind.to_vec(), data.to_vec()));
+            rest = &rest[12 + len..];
+        }
+
+        let kinds = chunks.iter().map(|(k, _)| k.as_slice()).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![b"IHDR".as_slice(), b"IDAT".as_slice(), b"IEND".as_slice()]
+        );
+
+        let ihdr = &chunks[0].1;
+        assert_eq!(
+            u32::from_be_bytes(ihdr[..4].try_into().unwrap()),
+            WIDTH as u32
+        );
+        assert_eq!(
+            u32::from_be_bytes(ihdr[4..8].try_into().unwrap()),
+            HEIGHT as u32
+        );
+        assert_eq!(&ihdr[8..], &[8, 2, 0, 0, 0]);
+
+        let mut raw = Vec::new();
+        ZlibDecoder::new(chunks[1].1.as_slice())
+            .read_to_end(&mut raw)
+            .unwrap();
+
+        assert_eq!(raw.len(), (WIDTH * 3 + 1) * HEIGHT);
+        raw
+    }
+
+    fn pixel(raw: &[u8], x: usize, y: usize) -> [u8; 3] {
+        let row = &raw[y * (WIDTH * 3 + 1)..(y + 1) * (WIDTH * 3 + 1)];
+        assert_eq!(row[0], 0, "scanline should not be filtered");
+
+        let i = 1 + x * 3;
+        [row[i], row[i + 1], row[i + 2]]
+    }
+
+    #[test]
+    fn empty_chart_has_grid() {
+        let raw = decode(&bar_chart(&[]).unwrap());
+
+        assert_eq!(pixel(&raw, MARGIN, MARGIN), GRID);
+        assert_eq!(pixel(&raw, MARGIN, HEIGHT - MARGIN), GRID);
+        assert_eq!(pixel(&raw, 0, 0), BACKGROUND);
+    }
+
+    #[test]
+    fn bars_scale_to_largest_value() {
+        let red = [0xff, 0, 0];
+        let raw = decode(
+            &bar_chart(&[Series {
+                values: &[2, 1],
+                color: red,
+            }])
+            .unwrap(),
+        );
+
+        // Two groups of 264 pixels, each with a centered bar of 198 pixels
+        let (first, second) = (MARGIN + 33 + 1, MARGIN + 264 + 33 + 1);
+
+        // The largest bar fills the plot, the other one half of it
+        assert_eq!(pixel(&raw, first, MARGIN + 10), red);
+        assert_eq!(pixel(&raw, second, MARGIN + 10), BACKGROUND);
+        assert_eq!(pixel(&raw, second, HEIGHT - MARGIN - 10), red);
+
+        // Padding between the bars
+        assert_eq!(pixel(&raw, MARGIN + 1, HEIGHT - MARGIN - 10), BACKGROUND);
+    }
+
+    #[test]
+    fn more_bars_than_pixels() {
+        let values = vec![1; WIDTH * 2];
+
+        let raw = decode(
+            &bar_chart(&[
+                Series {
+                    values: &values,
+                    color: [0xff, 0, 0],
+                },
+                Series {
+                    values: &values,
+                    color: [0, 0xff, 0],
+                },
+            ])
+            .unwrap(),
+        );
+
+        assert_eq!(pixel(&raw, 0, 0), BACKGROUND);
+    }
+}
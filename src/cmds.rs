@@ -1,8 +1,18 @@
+use log::warn;
 use poise::{
-    serenity_prelude::{CreateEmbed, Member},
+    serenity_prelude::{
+        ButtonStyle, ComponentInteractionDataKind, CreateActionRow, CreateButton, CreateEmbed,
+        CreateEmbedFooter, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption,
+        GuildChannel, GuildId, Member, Role,
+    },
     CreateReply,
 };
-use serenity::{all::UserId, builder::CreateAttachment, prelude::Mentionable};
+use serde_json::json;
+use serenity::{
+    all::{User, UserId},
+    prelude::Mentionable,
+};
+use strum::VariantNames;
 
 use crate::{Context, Error};
 
@@ -16,7 +26,16 @@ pub async fn ping(ctx: Context<'_>) -> Result<(), Error> {
 #[poise::command(
     prefix_command,
     slash_command,
-    subcommands("add_admin", "remove_admin")
+    subcommands(
+        "add_admin",
+        "remove_admin",
+        "add_admin_role",
+        "remove_admin_role",
+        "admins",
+        "grant",
+        "revoke",
+        "capabilities"
+    )
 )]
 pub async fn perms(_ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
@@ -44,14 +63,27 @@ pub async fn add_admin(ctx: Context<'_>, user: Member) -> Result<(), Error> {
         return Err("User is already an admin".into());
     }
 
+    let mut tx = ctx.data().pool.begin().await?;
+
     sqlx::query!(
         "INSERT INTO guild_admins (guild_id, user_id) VALUES ($1, $2)",
         ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
         user.user.id.to_string()
     )
-    .execute(&ctx.data().pool)
+    .execute(&mut *tx)
+    .await?;
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        None,
+        Some(json!({ "user_id": user.user.id.to_string() })),
+    )
     .await?;
 
+    tx.commit().await?;
+    log.post(ctx).await;
+
     ctx.say("Added admin successfully").await?;
 
     Ok(())
@@ -79,25 +111,384 @@ pub async fn remove_admin(ctx: Context<'_>, user: Member) -> Result<(), Error> {
         return Err("User is not already an admin?".into());
     }
 
+    let mut tx = ctx.data().pool.begin().await?;
+
     sqlx::query!(
         "DELETE FROM guild_admins WHERE guild_id = $1 AND user_id = $2",
         ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
         user.user.id.to_string()
     )
-    .execute(&ctx.data().pool)
+    .execute(&mut *tx)
     .await?;
 
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(json!({ "user_id": user.user.id.to_string() })),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
     ctx.say("Removed admin successfully").await?;
 
     Ok(())
 }
 
+/// Allow all members with a role to act as admins
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn add_admin_role(ctx: Context<'_>, role: Role) -> Result<(), Error> {
+    // Check that user is guild owner
+    if ctx.author().id != ctx.guild().ok_or("Could not get guild id")?.owner_id {
+        return Err("Only guild owners can add admin roles".into());
+    }
+
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    if role.id.get() == guild_id.get() {
+        return Err("The @everyone role cannot be made an admin role".into());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let res = sqlx::query!(
+        "INSERT INTO guild_admin_roles (guild_id, role_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        guild_id.to_string(),
+        role.id.to_string()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err("Role is already an admin role".into());
+    }
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        None,
+        Some(json!({ "role_id": role.id.to_string() })),
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.say(format!("Members with {} are now admins", role.mention()))
+        .await?;
+
+    Ok(())
+}
+
+/// Stop a role from granting admin
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn remove_admin_role(ctx: Context<'_>, role: Role) -> Result<(), Error> {
+    // Check that user is guild owner
+    if ctx.author().id != ctx.guild().ok_or("Could not get guild id")?.owner_id {
+        return Err("Only guild owners can remove admin roles".into());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let res = sqlx::query!(
+        "DELETE FROM guild_admin_roles WHERE guild_id = $1 AND role_id = $2",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        role.id.to_string()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err("Role is not an admin role?".into());
+    }
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(json!({ "role_id": role.id.to_string() })),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.say("Removed admin role successfully").await?;
+
+    Ok(())
+}
+
+/// View the admins and admin roles of the server
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn admins(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    let users = sqlx::query!(
+        "SELECT user_id FROM guild_admins WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    let roles = sqlx::query!(
+        "SELECT role_id FROM guild_admin_roles WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    let users = users
+        .iter()
+        .map(|u| format!("<@{}>", u.user_id))
+        .collect::<Vec<_>>();
+
+    let roles = roles
+        .iter()
+        .map(|r| format!("<@&{}>", r.role_id))
+        .collect::<Vec<_>>();
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::new()
+                .title("Admins")
+                .field(
+                    "Users",
+                    if users.is_empty() {
+                        "None".to_string()
+                    } else {
+                        users.join(", ")
+                    },
+                    false,
+                )
+                .field(
+                    "Roles",
+                    if roles.is_empty() {
+                        "None".to_string()
+                    } else {
+                        roles.join(", ")
+                    },
+                    false,
+                )
+                .color(0x00ff00),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the ID and type of a user or role given to a command, exactly one must be set
+fn user_or_role(user: Option<User>, role: Option<Role>) -> Result<(String, &'static str), Error> {
+    match (user, role) {
+        (Some(user), None) => Ok((user.id.to_string(), "user")),
+        (None, Some(role)) => Ok((role.id.to_string(), "role")),
+        _ => Err("Please specify either a user or a role".into()),
+    }
+}
+
+/// Formats a stored user or role as a mention
+fn mention_target(target_id: &str, target_type: &str) -> String {
+    if target_type == "role" {
+        format!("<@&{}>", target_id)
+    } else {
+        format!("<@{}>", target_id)
+    }
+}
+
+/// Grant a capability to a user or role
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn grant(
+    ctx: Context<'_>,
+    #[description = "The capability to grant"] capability: crate::core::CapabilityChoices,
+    #[description = "The user to grant the capability to"] user: Option<User>,
+    #[description = "The role to grant the capability to"] role: Option<Role>,
+) -> Result<(), Error> {
+    let capability = capability.resolve();
+
+    // Only the owner can hand out the ability to hand out capabilities
+    if capability == crate::core::Capability::ManageAdmins
+        && ctx.author().id != ctx.guild().ok_or("Could not get guild id")?.owner_id
+    {
+        return Err("Only guild owners can grant the Manage Admins capability".into());
+    }
+
+    let (target_id, target_type) = user_or_role(user, role)?;
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let res = sqlx::query!(
+        "
+            INSERT INTO guild_capabilities (guild_id, target_id, target_type, capability)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT DO NOTHING
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        target_id,
+        target_type,
+        capability.to_string()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err("This capability has already been granted".into());
+    }
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        None,
+        Some(json!({
+            "target_id": target_id,
+            "target_type": target_type,
+            "capability": capability.to_string(),
+        })),
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.say(format!(
+        "Granted ``{}`` to {}",
+        capability.to_cond(),
+        mention_target(&target_id, target_type)
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Revoke a capability from a user or role
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn revoke(
+    ctx: Context<'_>,
+    #[description = "The capability to revoke"] capability: crate::core::CapabilityChoices,
+    #[description = "The user to revoke the capability from"] user: Option<User>,
+    #[description = "The role to revoke the capability from"] role: Option<Role>,
+) -> Result<(), Error> {
+    let capability = capability.resolve();
+
+    if capability == crate::core::Capability::ManageAdmins
+        && ctx.author().id != ctx.guild().ok_or("Could not get guild id")?.owner_id
+    {
+        return Err("Only guild owners can revoke the Manage Admins capability".into());
+    }
+
+    let (target_id, target_type) = user_or_role(user, role)?;
+
+    // Revoking a capability can lock out the moderators defending the server
+    if !crate::safeguards::allow_weakening(
+        ctx,
+        &format!("revoke {} from {} {}", capability, target_type, target_id),
+    )
+    .await?
+    {
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let res = sqlx::query!(
+        "DELETE FROM guild_capabilities WHERE guild_id = $1 AND target_id = $2 AND capability = $3",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        target_id,
+        capability.to_string()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err("This capability has not been granted?".into());
+    }
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(json!({
+            "target_id": target_id,
+            "target_type": target_type,
+            "capability": capability.to_string(),
+        })),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.say(format!(
+        "Revoked ``{}`` from {}",
+        capability.to_cond(),
+        mention_target(&target_id, target_type)
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// View the capabilities granted in this server
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn capabilities(ctx: Context<'_>) -> Result<(), Error> {
+    let grants = sqlx::query!(
+        "
+            SELECT target_id, target_type, capability
+            FROM guild_capabilities
+            WHERE guild_id = $1
+            ORDER BY capability
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string()
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    if grants.is_empty() {
+        ctx.say("No capabilities have been granted, only admins can use Skynet commands")
+            .await?;
+        return Ok(());
+    }
+
+    let mut embed = CreateEmbed::new().title("Capabilities").color(0x00ff00);
+
+    for variant in crate::core::Capability::VARIANTS {
+        let capability = variant.parse::<crate::core::Capability>()?;
+
+        let holders = grants
+            .iter()
+            .filter(|g| g.capability == *variant)
+            .map(|g| mention_target(&g.target_id, &g.target_type))
+            .collect::<Vec<_>>();
+
+        if holders.is_empty() {
+            continue;
+        }
+
+        embed = embed.field(capability.to_cond(), holders.join(", "), false);
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
 /// Limits base command
 #[poise::command(
     prefix_command,
     slash_command,
     guild_only,
-    subcommands("limits_add", "limits_view", "limits_remove", "hit_limits")
+    subcommands(
+        "limits_add",
+        "limits_view",
+        "limits_remove",
+        "limits_protect",
+        "limits_unprotect",
+        "limits_weight",
+        "limits_weights",
+        "limits_preset",
+        "hit_limits"
+    )
 )]
 pub async fn limits(_ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
@@ -119,12 +510,22 @@ pub async fn limits_add(
     limit_time_unit: crate::utils::Unit,
     #[description = "The action to take when the limit is hit"]
     limit_action: crate::core::UserLimitActionsChoices,
+    #[description = "Only count actions on this channel or role (more can be added with /limits protect)"]
+    #[autocomplete = "crate::autocompletes::targets_autocomplete"]
+    limit_target: Option<String>,
 ) -> Result<(), Error> {
     let limit_type = limit_type.resolve();
     let limit_action = limit_action.resolve();
 
+    let limit_targets = match limit_target {
+        Some(target) => vec![parse_target(&target)?],
+        None => Vec::new(),
+    };
+
+    let mut tx = ctx.data().pool.begin().await?;
+
     // Add limit to db
-    sqlx::query!(
+    let rec = sqlx::query!(
         "
             INSERT INTO limits (
                 guild_id,
@@ -132,7 +533,8 @@ pub async fn limits_add(
                 limit_type,
                 limit_action,
                 limit_per,
-                limit_time
+                limit_time,
+                limit_targets
             )
             VALUES (
                 $1, 
@@ -140,24 +542,243 @@ pub async fn limits_add(
                 $3, 
                 $4, 
                 $5,
-                make_interval(secs => $6)
+                make_interval(secs => $6),
+                $7
             )
+            RETURNING to_jsonb(limits) AS \"limit!\"
         ",
         ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
         limit_name,
         limit_type.to_string(),
         limit_action.to_string(),
         limit_per,
-        (limit_time * limit_time_unit.to_seconds()) as f64
+        (limit_time * limit_time_unit.to_seconds()) as f64,
+        &limit_targets
     )
-    .execute(&ctx.data().pool)
+    .fetch_one(&mut *tx)
     .await?;
 
+    let log = crate::audit::record(ctx, &mut tx, None, Some(rec.limit)).await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
     ctx.say("Added limit successfully").await?;
 
     Ok(())
 }
 
+/// Apply a built-in preset of limits, previewing the changes first
+#[poise::command(prefix_command, slash_command, guild_only, rename = "preset")]
+pub async fn limits_preset(
+    ctx: Context<'_>,
+    #[description = "The preset to apply"] preset: crate::presets::PresetChoices,
+) -> Result<(), Error> {
+    use crate::presets::PresetChange;
+
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+    let preset = preset.resolve();
+
+    let existing = crate::core::Limit::from_guild(&ctx.data().pool, guild_id).await?;
+    let changes = preset.diff(&existing);
+
+    let mut added = Vec::new();
+    let mut updated = Vec::new();
+    let mut unchanged = Vec::new();
+
+    for change in changes.iter() {
+        match change {
+            PresetChange::Add(new) => added.push(format!(
+                "- ``{}`` {}",
+                new.limit_type.to_cond(),
+                new.describe()
+            )),
+            PresetChange::Update { old, new, .. } => updated.push(format!(
+                "- ``{}`` {} **->** {}{}",
+                new.limit_type.to_cond(),
+                old.describe(),
+                new.describe(),
+                if change.weakens() { " (weaker)" } else { "" }
+            )),
+            PresetChange::Unchanged(old) => {
+                unchanged.push(format!("- ``{}``", old.limit_type.to_cond()))
+            }
+        }
+    }
+
+    if added.is_empty() && updated.is_empty() {
+        ctx.say(format!(
+            "Your limits already match the ``{}`` preset",
+            preset.to_cond()
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let mut preview = String::new();
+
+    for (title, lines) in [
+        ("Added", &added),
+        ("Changed", &updated),
+        ("Unchanged", &unchanged),
+    ] {
+        if !lines.is_empty() {
+            preview.push_str(&format!("**{}**\n{}\n\n", title, lines.join("\n")));
+        }
+    }
+
+    preview.push_str("Limits scoped to specific channels or roles are not affected");
+
+    let handle = ctx
+        .send(
+            CreateReply::default()
+                .embed(
+                    CreateEmbed::default()
+                        .title(format!("Apply {} Preset?", preset.to_cond()))
+                        .description(preview)
+                        .color(0x00ff00),
+                )
+                .components(vec![CreateActionRow::Buttons(vec![
+                    CreateButton::new("preset_apply")
+                        .label("Apply")
+                        .style(ButtonStyle::Success),
+                    CreateButton::new("preset_cancel")
+                        .label("Cancel")
+                        .style(ButtonStyle::Danger),
+                ])]),
+        )
+        .await?;
+
+    let msg_id = handle.message().await?.id;
+
+    let interaction = crate::utils::next_component(ctx, msg_id, 120).await?;
+
+    if !interaction.is_some_and(|i| i.data.custom_id.as_str() == "preset_apply") {
+        handle
+            .edit(
+                ctx,
+                CreateReply::default()
+                    .content("Preset not applied")
+                    .components(vec![]),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    handle
+        .edit(
+            ctx,
+            CreateReply::default()
+                .content("Applying preset...")
+                .components(vec![]),
+        )
+        .await?;
+
+    if changes.iter().any(|c| c.weakens())
+        && !crate::safeguards::allow_weakening(
+            ctx,
+            &format!("apply the {} limit preset", preset.to_cond()),
+        )
+        .await?
+    {
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    for change in changes.iter() {
+        match change {
+            PresetChange::Add(new) => {
+                sqlx::query!(
+                    "
+                        INSERT INTO limits (
+                            guild_id,
+                            limit_name,
+                            limit_type,
+                            limit_action,
+                            limit_per,
+                            limit_time
+                        )
+                        VALUES (
+                            $1,
+                            $2,
+                            $3,
+                            $4,
+                            $5,
+                            make_interval(secs => $6)
+                        )
+                    ",
+                    guild_id.to_string(),
+                    new.limit_name,
+                    new.limit_type.to_string(),
+                    new.limit_action.to_string(),
+                    new.limit_per,
+                    new.limit_time as f64
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            PresetChange::Update { limit_id, new, .. } => {
+                sqlx::query!(
+                    "
+                        UPDATE limits
+                        SET limit_action = $3,
+                        limit_per = $4,
+                        limit_time = make_interval(secs => $5)
+                        WHERE guild_id = $1
+                        AND limit_id = $2
+                    ",
+                    guild_id.to_string(),
+                    limit_id,
+                    new.limit_action.to_string(),
+                    new.limit_per,
+                    new.limit_time as f64
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            PresetChange::Unchanged(_) => {}
+        }
+    }
+
+    let before = changes
+        .iter()
+        .filter_map(|c| match c {
+            PresetChange::Update { old, .. } => Some(old),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let after = changes
+        .iter()
+        .filter_map(|c| match c {
+            PresetChange::Add(new) | PresetChange::Update { new, .. } => Some(new),
+            PresetChange::Unchanged(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(json!({ "limits": before })),
+        Some(json!({ "preset": preset.to_string(), "limits": after })),
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.say(format!(
+        "Applied the ``{}`` preset: {} limits added, {} limits changed",
+        preset.to_cond(),
+        added.len(),
+        updated.len()
+    ))
+    .await?;
+
+    Ok(())
+}
+
 /// View the limits setup for this server
 #[poise::command(prefix_command, slash_command, guild_only, rename = "view")]
 pub async fn limits_view(ctx: Context<'_>) -> Result<(), Error> {
@@ -191,18 +812,30 @@ pub async fn limits_view(ctx: Context<'_>) -> Result<(), Error> {
             embeds.push(CreateEmbed::default().title("Limits").color(0x00ff00));
         }
 
-        embeds[i] = embeds[i].clone().field(
-            limit.limit_name,
-            format!(
-                "If over {amount} ``{cond}`` triggered between {time} interval: ``{then}`` [{id}]",
-                amount = limit.limit_per,
-                cond = limit.limit_type.to_cond(),
-                time = crate::utils::parse_pg_interval(limit.limit_time),
-                then = limit.limit_action.to_cond(),
-                id = limit.limit_id
-            ),
-            false,
+        let mut value = format!(
+            "If over {amount} ``{cond}`` triggered between {time} interval: ``{then}`` [{id}]",
+            amount = limit.limit_per,
+            cond = limit.limit_type.to_cond(),
+            time = crate::utils::parse_pg_interval(limit.limit_time),
+            then = limit.limit_action.to_cond(),
+            id = limit.limit_id
         );
+
+        if !limit.limit_targets.is_empty() {
+            let guild = ctx.guild().ok_or("Could not get guild from cache")?;
+
+            value.push_str(&format!(
+                "\n**Protected Targets:** {}",
+                limit
+                    .limit_targets
+                    .iter()
+                    .map(|t| crate::utils::format_target(&guild, t))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        embeds[i] = embeds[i].clone().field(limit.limit_name, value, false);
     }
 
     for embed in embeds {
@@ -239,117 +872,1631 @@ pub async fn limits_remove(
         return Err("Could not find limit".into());
     }
 
+    if !crate::safeguards::allow_weakening(ctx, &format!("remove limit {}", limit_id)).await? {
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
     // Remove limit
-    sqlx::query!(
+    let rec = sqlx::query!(
         "
             DELETE FROM limits
             WHERE guild_id = $1
             AND limit_id = $2
+            RETURNING to_jsonb(limits) AS \"limit!\"
         ",
         ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
         limit_id
     )
-    .execute(&ctx.data().pool)
+    .fetch_one(&mut *tx)
     .await?;
 
+    let log = crate::audit::record(ctx, &mut tx, Some(rec.limit), None).await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    crate::safeguards::record_deletion(ctx, format!("limit:{}", limit_id)).await?;
+
     ctx.say("Removed limit successfully").await?;
 
     Ok(())
 }
 
-/// Setup the bot if it is not already setup
-#[poise::command(prefix_command, slash_command, guild_only)]
-pub async fn setup(ctx: Context<'_>) -> Result<(), Error> {
-    // Check if guild is already setup
-    let count = sqlx::query!(
-        "SELECT COUNT(*) FROM guilds WHERE guild_id = $1",
-        ctx.guild_id().ok_or("Could not get guild id")?.to_string()
+/// Only count actions on a specific channel or role towards a limit
+#[poise::command(prefix_command, slash_command, guild_only, rename = "protect")]
+pub async fn limits_protect(
+    ctx: Context<'_>,
+    #[description = "The limit id to scope"]
+    #[autocomplete = "crate::autocompletes::limits_autocomplete"]
+    limit_id: String,
+    #[description = "The channel or role to protect"]
+    #[autocomplete = "crate::autocompletes::targets_autocomplete"]
+    target: String,
+) -> Result<(), Error> {
+    let target = parse_target(&target)?;
+
+    let current = limit_targets(ctx, &limit_id).await?;
+
+    // A limit without targets covers every target, so scoping it narrows what it protects
+    if current.is_empty()
+        && !crate::safeguards::allow_weakening(
+            ctx,
+            &format!("scope limit {} to {}", limit_id, target),
+        )
+        .await?
+    {
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let res = sqlx::query!(
+        "
+            UPDATE limits
+            SET limit_targets = array_append(limit_targets, $3)
+            WHERE guild_id = $1
+            AND limit_id = $2
+            AND NOT($3 = ANY(limit_targets))
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        limit_id,
+        target
     )
-    .fetch_one(&ctx.data().pool)
-    .await
-    .map_err(|_| "Could not fetch guild status")?;
+    .execute(&mut *tx)
+    .await?;
 
-    if count.count.unwrap_or_default() > 0 {
-        return Err("Guild is already setup".into());
+    if res.rows_affected() == 0 {
+        return Err("Could not find limit or target is already protected by it".into());
     }
 
-    // Add guild to db
-    sqlx::query!(
-        "INSERT INTO guilds (guild_id) VALUES ($1)",
-        ctx.guild_id().ok_or("Could not get guild id")?.to_string()
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        None,
+        Some(json!({ "limit_id": limit_id, "limit_target": target })),
     )
-    .execute(&ctx.data().pool)
     .await?;
 
-    ctx.say("Setup successfully. Now you can add limits for SkyNet to monitor for")
-        .await?;
+    tx.commit().await?;
+    log.post(ctx).await;
 
-    Ok(())
-}
+    ctx.say("Target is now protected by this limit").await?;
 
-/// Action management
-#[poise::command(prefix_command, slash_command, guild_only, subcommands("actions_view"))]
-pub async fn actions(_ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-/// View actions taken by users that have been recorded by SkyNet
-#[poise::command(prefix_command, slash_command, guild_only, rename = "view")]
-pub async fn actions_view(
+/// Stop scoping a limit to a channel or role
+#[poise::command(prefix_command, slash_command, guild_only, rename = "unprotect")]
+pub async fn limits_unprotect(
     ctx: Context<'_>,
-    #[description = "User ID (optional)"] user_id: Option<UserId>,
+    #[description = "The limit id to unscope"]
+    #[autocomplete = "crate::autocompletes::limits_autocomplete"]
+    limit_id: String,
+    #[description = "The channel or role to stop protecting"]
+    #[autocomplete = "crate::autocompletes::targets_autocomplete"]
+    target: String,
 ) -> Result<(), Error> {
-    let actions = {
-        if let Some(user_id) = user_id {
-            crate::core::Action::user(
-                &ctx.data().pool,
-                ctx.guild_id().ok_or("Could not get guild id")?,
-                user_id,
-            )
-            .await?
-        } else {
-            crate::core::Action::guild(
-                &ctx.data().pool,
-                ctx.guild_id().ok_or("Could not get guild id")?,
-            )
-            .await?
-        }
-    };
-
-    if actions.is_empty() {
-        ctx.say("No actions recorded").await?;
+    let target = parse_target(&target)?;
+
+    let current = limit_targets(ctx, &limit_id).await?;
+
+    // Removing the last target makes the limit cover every target again, which is not weaker
+    if current.iter().any(|t| *t != target)
+        && !crate::safeguards::allow_weakening(
+            ctx,
+            &format!("stop limit {} from protecting {}", limit_id, target),
+        )
+        .await?
+    {
         return Ok(());
     }
 
-    if actions.len() > 30 {
-        let actions = serde_json::to_string(&actions).map_err(|_| "Could not serialize actions")?;
-
-        // Create a attachment
-        let attachment = CreateAttachment::bytes(actions.into_bytes(), "actions.json");
+    let mut tx = ctx.data().pool.begin().await?;
 
-        ctx.send(CreateReply::default().attachment(attachment)).await?;
+    let res = sqlx::query!(
+        "
+            UPDATE limits
+            SET limit_targets = array_remove(limit_targets, $3)
+            WHERE guild_id = $1
+            AND limit_id = $2
+            AND $3 = ANY(limit_targets)
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        limit_id,
+        target
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err("Could not find limit or target is not protected by it".into());
+    }
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(json!({ "limit_id": limit_id, "limit_target": target })),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.say("Target is no longer protected by this limit. If no targets remain, the limit applies to all targets again").await?;
+
+    Ok(())
+}
+
+/// Fetch the targets a limit is scoped to
+async fn limit_targets(ctx: Context<'_>, limit_id: &str) -> Result<Vec<String>, Error> {
+    let rec = sqlx::query!(
+        "SELECT limit_targets FROM limits WHERE guild_id = $1 AND limit_id = $2",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        limit_id
+    )
+    .fetch_optional(&ctx.data().pool)
+    .await?
+    .ok_or("Could not find limit")?;
+
+    Ok(rec.limit_targets)
+}
+
+/// Checks that a limit target is a valid channel or role ID
+fn parse_target(target: &str) -> Result<String, Error> {
+    target
+        .parse::<u64>()
+        .map(|id| id.to_string())
+        .map_err(|_| "Target must be a channel or role ID".into())
+}
+
+/// Set how much an action type counts towards threat score limits
+#[poise::command(prefix_command, slash_command, guild_only, rename = "weight")]
+pub async fn limits_weight(
+    ctx: Context<'_>,
+    #[description = "The type of action to weigh"]
+    limit_type: crate::core::UserLimitTypesChoices,
+    #[description = "How much a single action of this type adds to a users threat score"]
+    weight: i32,
+) -> Result<(), Error> {
+    let limit_type = limit_type.resolve();
+
+    if limit_type == crate::core::UserLimitTypes::ThreatScore {
+        return Err("Threat scores are computed from the other action types and cannot be weighed".into());
+    }
+
+    if weight < 0 {
+        return Err("Weight cannot be negative".into());
+    }
+
+    let old = sqlx::query!(
+        "SELECT weight FROM limit_weights WHERE guild_id = $1 AND limit_type = $2",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        limit_type.to_string()
+    )
+    .fetch_optional(&ctx.data().pool)
+    .await?;
+
+    // Unweighted action types count as 1
+    let old_weight = old.map(|o| o.weight).unwrap_or(1);
+
+    if weight < old_weight
+        && !crate::safeguards::allow_weakening(
+            ctx,
+            &format!("lower the weight of {} to {}", limit_type, weight),
+        )
+        .await?
+    {
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    sqlx::query!(
+        "
+            INSERT INTO limit_weights (guild_id, limit_type, weight)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id, limit_type) DO UPDATE SET weight = EXCLUDED.weight
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        limit_type.to_string(),
+        weight
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(json!({ "limit_type": limit_type.to_string(), "weight": old_weight })),
+        Some(json!({ "limit_type": limit_type.to_string(), "weight": weight })),
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.say(format!(
+        "``{}`` now adds {} to a users threat score",
+        limit_type.to_cond(),
+        weight
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// View the threat score weights for this server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "weights")]
+pub async fn limits_weights(ctx: Context<'_>) -> Result<(), Error> {
+    let weights = crate::core::LimitWeight::from_guild(
+        &ctx.data().pool,
+        ctx.guild_id().ok_or("Could not get guild id")?,
+    )
+    .await?;
+
+    let mut embed = CreateEmbed::default()
+        .title("Threat Score Weights")
+        .description("Every action a user takes adds its weight to their threat score. Threat score limits are hit once the score over the interval reaches the limit")
+        .color(0x00ff00);
+
+    for variant in crate::core::UserLimitTypes::VARIANTS {
+        let limit_type: crate::core::UserLimitTypes = variant.parse()?;
+
+        if limit_type == crate::core::UserLimitTypes::ThreatScore {
+            continue;
+        }
+
+        let weight = weights
+            .iter()
+            .find(|w| w.limit_type == limit_type)
+            .map(|w| w.weight)
+            .unwrap_or(1);
+
+        embed = embed.field(limit_type.to_cond(), weight.to_string(), true);
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Server settings
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands(
+        "settings_log_channel",
+        "settings_quarantine_role",
+        "settings_dm_alerts",
+        "settings_safeguards"
+    )
+)]
+pub async fn settings(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Set the channel SkyNet posts logs to
+#[poise::command(prefix_command, slash_command, guild_only, rename = "log_channel")]
+pub async fn settings_log_channel(
+    ctx: Context<'_>,
+    #[description = "The channel to post logs to, leave empty to disable logging"]
+    channel: Option<GuildChannel>,
+) -> Result<(), Error> {
+    let old = sqlx::query!(
+        "SELECT log_channel FROM guilds WHERE guild_id = $1",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string()
+    )
+    .fetch_one(&ctx.data().pool)
+    .await?;
+
+    if channel.is_none()
+        && old.log_channel.is_some()
+        && !crate::safeguards::allow_weakening(ctx, "disable logging").await?
+    {
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    sqlx::query!(
+        "UPDATE guilds SET log_channel = $2 WHERE guild_id = $1",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        channel.as_ref().map(|c| c.id.to_string())
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(json!({ "log_channel": old.log_channel })),
+        Some(json!({ "log_channel": channel.as_ref().map(|c| c.id.to_string()) })),
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    match channel {
+        Some(channel) => ctx.say(format!("Logs will now be posted to {}", channel.mention())).await?,
+        None => ctx.say("Logging disabled").await?,
+    };
+
+    Ok(())
+}
+
+/// Set the role given to quarantined members
+#[poise::command(prefix_command, slash_command, guild_only, rename = "quarantine_role")]
+pub async fn settings_quarantine_role(
+    ctx: Context<'_>,
+    #[description = "The role to give quarantined members, leave empty to unset"]
+    role: Option<Role>,
+) -> Result<(), Error> {
+    let old = sqlx::query!(
+        "SELECT quarantine_role FROM guilds WHERE guild_id = $1",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string()
+    )
+    .fetch_one(&ctx.data().pool)
+    .await?;
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    sqlx::query!(
+        "UPDATE guilds SET quarantine_role = $2 WHERE guild_id = $1",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        role.as_ref().map(|r| r.id.to_string())
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(json!({ "quarantine_role": old.quarantine_role })),
+        Some(json!({ "quarantine_role": role.as_ref().map(|r| r.id.to_string()) })),
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    match role {
+        Some(role) => ctx.say(format!("Quarantined members will now be given {}", role.mention())).await?,
+        None => ctx.say("Quarantine role unset").await?,
+    };
+
+    Ok(())
+}
+
+/// Choose when the server owner and admins are alerted by DM
+#[poise::command(prefix_command, slash_command, guild_only, rename = "dm_alerts")]
+pub async fn settings_dm_alerts(
+    ctx: Context<'_>,
+    #[description = "The minimum severity to send DMs for, leave empty to disable DMs"]
+    severity: Option<crate::core::SeverityChoices>,
+) -> Result<(), Error> {
+    let severity = severity.map(|s| s.resolve());
+
+    let old = sqlx::query!(
+        "SELECT dm_severity FROM guilds WHERE guild_id = $1",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string()
+    )
+    .fetch_one(&ctx.data().pool)
+    .await?;
+
+    let old_severity = old
+        .dm_severity
+        .as_ref()
+        .map(|s| s.parse::<crate::core::Severity>())
+        .transpose()?;
+
+    // Fewer alerts means a nuke may go unnoticed
+    let weakens = match (&old_severity, &severity) {
+        (Some(_), None) => true,
+        (Some(old), Some(new)) => new > old,
+        (None, _) => false,
+    };
+
+    if weakens && !crate::safeguards::allow_weakening(ctx, "reduce DM alerts").await? {
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    sqlx::query!(
+        "UPDATE guilds SET dm_severity = $2 WHERE guild_id = $1",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        severity.as_ref().map(|s| s.to_string())
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(json!({ "dm_severity": old.dm_severity })),
+        Some(json!({ "dm_severity": severity.as_ref().map(|s| s.to_string()) })),
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    match severity {
+        Some(severity) => ctx.say(format!("The owner and admins will now be sent DMs for ``{}`` or more severe alerts", severity.to_cond())).await?,
+        None => ctx.say("DM alerts disabled").await?,
+    };
+
+    Ok(())
+}
+
+/// Hold back changes that weaken protection unless they are made by the owner
+#[poise::command(prefix_command, slash_command, guild_only, rename = "safeguards")]
+pub async fn settings_safeguards(
+    ctx: Context<'_>,
+    #[description = "How weakening changes are held back, leave empty to disable safeguards"]
+    mode: Option<crate::core::SafeguardModeChoices>,
+    #[description = "How long weakening changes must wait in cooldown mode"]
+    cooldown: Option<i64>,
+    #[description = "The time unit for the cooldown [seconds/minutes/hours/days]"]
+    cooldown_unit: Option<crate::utils::Unit>,
+) -> Result<(), Error> {
+    // Only the owner is exempt from safeguards, so only they may change them
+    if ctx.author().id != ctx.guild().ok_or("Could not get guild id")?.owner_id {
+        return Err("Only guild owners can change safeguards".into());
+    }
+
+    let mode = mode.map(|m| m.resolve());
+
+    let cooldown = match (cooldown, cooldown_unit) {
+        (Some(cooldown), Some(unit)) => Some((cooldown * unit.to_seconds()) as f64),
+        (Some(_), None) => return Err("Please provide a time unit for the cooldown".into()),
+        (None, _) => None,
+    };
+
+    let old = sqlx::query!(
+        "SELECT safeguard_mode, safeguard_cooldown FROM guilds WHERE guild_id = $1",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string()
+    )
+    .fetch_one(&ctx.data().pool)
+    .await?;
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let rec = sqlx::query!(
+        "
+            UPDATE guilds
+            SET safeguard_mode = $2,
+            safeguard_cooldown = COALESCE(make_interval(secs => $3), safeguard_cooldown)
+            WHERE guild_id = $1
+            RETURNING safeguard_cooldown
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        mode.as_ref().map(|m| m.to_string()),
+        cooldown
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(json!({
+            "safeguard_mode": old.safeguard_mode,
+            "safeguard_cooldown": crate::utils::pg_interval_to_secs(&old.safeguard_cooldown),
+        })),
+        Some(json!({
+            "safeguard_mode": mode.as_ref().map(|m| m.to_string()),
+            "safeguard_cooldown": crate::utils::pg_interval_to_secs(&rec.safeguard_cooldown),
+        })),
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    match mode {
+        Some(crate::core::SafeguardMode::Cooldown) => {
+            ctx.say(format!(
+                "Changes weakening protection now have to wait {} unless made by the owner",
+                crate::utils::parse_pg_interval(rec.safeguard_cooldown)
+            ))
+            .await?
+        }
+        Some(crate::core::SafeguardMode::OwnerConfirmation) => {
+            ctx.say("Changes weakening protection now have to be approved by the owner")
+                .await?
+        }
+        None => ctx.say("Safeguards disabled").await?,
+    };
+
+    Ok(())
+}
+
+/// Join rules
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("joinrules_add", "joinrules_view", "joinrules_remove")
+)]
+pub async fn joinrules(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Add a rule that joining members are checked against
+#[poise::command(prefix_command, slash_command, guild_only, rename = "add")]
+pub async fn joinrules_add(
+    ctx: Context<'_>,
+    #[description = "The name of the rule"]
+    rule_name: String,
+    #[description = "The action to take on members matching the rule"]
+    rule_action: crate::core::JoinRuleActionsChoices,
+    #[description = "Match accounts younger than this"]
+    min_account_age: Option<i64>,
+    #[description = "The time unit for the account age [seconds/minutes/hours/days]"]
+    min_account_age_unit: Option<crate::utils::Unit>,
+    #[description = "Match accounts with a default avatar"]
+    default_avatar: Option<bool>,
+    #[description = "Match usernames against this regex"]
+    username_pattern: Option<String>,
+) -> Result<(), Error> {
+    let rule_action = rule_action.resolve();
+
+    if min_account_age.is_some_and(|age| age < 0) {
+        return Err("Account age cannot be negative".into());
+    }
+
+    let min_account_age = match (min_account_age, min_account_age_unit) {
+        (Some(age), Some(unit)) => Some((age * unit.to_seconds()) as f64),
+        (Some(_), None) => return Err("Please provide a time unit for the account age".into()),
+        (None, _) => None,
+    };
+
+    let default_avatar = default_avatar.unwrap_or_default();
+
+    if let Some(ref pattern) = username_pattern {
+        regex::Regex::new(pattern).map_err(|e| format!("Invalid username pattern: {}", e))?;
+    }
+
+    if min_account_age.is_none() && !default_avatar && username_pattern.is_none() {
+        return Err("A join rule must have at least one condition".into());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let rec = sqlx::query!(
+        "
+            INSERT INTO join_rules (
+                guild_id,
+                rule_name,
+                rule_action,
+                min_account_age,
+                default_avatar,
+                username_pattern
+            )
+            VALUES (
+                $1,
+                $2,
+                $3,
+                make_interval(secs => $4),
+                $5,
+                $6
+            )
+            RETURNING to_jsonb(join_rules) AS \"rule!\"
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        rule_name,
+        rule_action.to_string(),
+        min_account_age,
+        default_avatar,
+        username_pattern
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let log = crate::audit::record(ctx, &mut tx, None, Some(rec.rule)).await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.say("Added join rule successfully").await?;
+
+    Ok(())
+}
+
+/// View the join rules setup for this server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "view")]
+pub async fn joinrules_view(ctx: Context<'_>) -> Result<(), Error> {
+    let rules = crate::core::JoinRule::from_guild(
+        &ctx.data().pool,
+        ctx.guild_id().ok_or("Could not get guild id")?,
+    )
+    .await?;
+
+    if rules.is_empty() {
+        ctx.say("No join rules setup for this server, use ``/joinrules add`` to add one!")
+            .await?;
+        return Ok(());
+    }
+
+    let mut embed = CreateEmbed::default().title("Join Rules").color(0x00ff00);
+
+    for rule in rules {
+        let mut conds = Vec::new();
+
+        if let Some(min_account_age) = rule.min_account_age {
+            conds.push(format!(
+                "account younger than {}",
+                crate::utils::parse_pg_interval(min_account_age)
+            ));
+        }
+
+        if rule.default_avatar {
+            conds.push("default avatar".to_string());
+        }
+
+        if let Some(pattern) = rule.username_pattern {
+            conds.push(format!("username matches ``{}``", pattern));
+        }
+
+        embed = embed.field(
+            rule.rule_name,
+            format!(
+                "If {conds}: ``{then}`` [{id}]",
+                conds = conds.join(" and "),
+                then = rule.rule_action.to_cond(),
+                id = rule.rule_id
+            ),
+            false,
+        );
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Remove a join rule from the server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "remove")]
+pub async fn joinrules_remove(
+    ctx: Context<'_>,
+    #[description = "The rule id to remove"]
+    #[autocomplete = "crate::autocompletes::join_rules_autocomplete"]
+    rule_id: String,
+) -> Result<(), Error> {
+    if !crate::safeguards::allow_weakening(ctx, &format!("remove join rule {}", rule_id)).await? {
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let rec = sqlx::query!(
+        "
+            DELETE FROM join_rules
+            WHERE guild_id = $1
+            AND rule_id = $2
+            RETURNING to_jsonb(join_rules) AS \"rule!\"
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        rule_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or("Could not find join rule")?;
+
+    let log = crate::audit::record(ctx, &mut tx, Some(rec.rule), None).await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    crate::safeguards::record_deletion(ctx, format!("join_rule:{}", rule_id)).await?;
+
+    ctx.say("Removed join rule successfully").await?;
+
+    Ok(())
+}
+
+/// Raid detection
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("raid_setup", "raid_view", "raid_disable")
+)]
+pub async fn raid(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Enable or update raid detection for the server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "setup")]
+pub async fn raid_setup(
+    ctx: Context<'_>,
+    #[description = "The amount of joins that are considered a raid"]
+    raid_joins: i32,
+    #[description = "The time interval joins are counted in"]
+    raid_time: i64,
+    #[description = "The time unit for the time interval [seconds/minutes/hours/days]"]
+    raid_time_unit: crate::utils::Unit,
+    #[description = "The action to take on members who joined during the raid"]
+    raid_action: crate::core::RaidActionsChoices,
+    #[description = "Raise the verification level of the server when a raid is detected"]
+    raise_verification: bool,
+    #[description = "Lock down the server when a raid is detected"]
+    lockdown: bool,
+) -> Result<(), Error> {
+    if raid_joins < 2 {
+        return Err("A raid must consist of at least 2 joins".into());
+    }
+
+    if raid_time <= 0 {
+        return Err("The time interval must be positive".into());
+    }
+
+    let raid_action = raid_action.resolve();
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    if let Some(current) = crate::core::RaidConfig::from_guild(&ctx.data().pool, guild_id).await? {
+        let new = crate::core::RaidConfig {
+            raid_joins,
+            raid_time: sqlx::postgres::types::PgInterval {
+                months: 0,
+                days: 0,
+                microseconds: raid_time * raid_time_unit.to_seconds() * 1_000_000,
+            },
+            raid_action: raid_action.clone(),
+            raise_verification,
+            lockdown,
+        };
+
+        if current.weakened_by(&new)
+            && !crate::safeguards::allow_weakening(ctx, "weaken raid detection").await?
+        {
+            return Ok(());
+        }
+    }
+
+    let old = sqlx::query!(
+        "SELECT to_jsonb(raid_config) AS \"config!\" FROM raid_config WHERE guild_id = $1",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string()
+    )
+    .fetch_optional(&ctx.data().pool)
+    .await?;
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let rec = sqlx::query!(
+        "
+            INSERT INTO raid_config (
+                guild_id,
+                raid_joins,
+                raid_time,
+                raid_action,
+                raise_verification,
+                lockdown
+            )
+            VALUES (
+                $1,
+                $2,
+                make_interval(secs => $3),
+                $4,
+                $5,
+                $6
+            )
+            ON CONFLICT (guild_id) DO UPDATE SET
+                raid_joins = EXCLUDED.raid_joins,
+                raid_time = EXCLUDED.raid_time,
+                raid_action = EXCLUDED.raid_action,
+                raise_verification = EXCLUDED.raise_verification,
+                lockdown = EXCLUDED.lockdown
+            RETURNING to_jsonb(raid_config) AS \"config!\"
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        raid_joins,
+        (raid_time * raid_time_unit.to_seconds()) as f64,
+        raid_action.to_string(),
+        raise_verification,
+        lockdown
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let log = crate::audit::record(ctx, &mut tx, old.map(|o| o.config), Some(rec.config)).await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.say("Raid detection setup successfully. Use ``/settings log_channel`` to get notified of raids")
+        .await?;
+
+    Ok(())
+}
+
+/// View the raid detection settings for this server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "view")]
+pub async fn raid_view(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(raid_config) = crate::core::RaidConfig::from_guild(
+        &ctx.data().pool,
+        ctx.guild_id().ok_or("Could not get guild id")?,
+    )
+    .await?
+    else {
+        ctx.say("Raid detection is not enabled, use ``/raid setup`` to enable it!")
+            .await?;
+        return Ok(());
+    };
+
+    let embed = CreateEmbed::default()
+        .title("Raid Detection")
+        .description(format!(
+            "If {amount} members join within {time}: ``{then}``",
+            amount = raid_config.raid_joins,
+            time = crate::utils::parse_pg_interval(raid_config.raid_time),
+            then = raid_config.raid_action.to_cond()
+        ))
+        .field(
+            "Raise Verification Level",
+            raid_config.raise_verification.to_string(),
+            true,
+        )
+        .field("Lockdown", raid_config.lockdown.to_string(), true)
+        .color(0x00ff00);
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Disable raid detection for the server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "disable")]
+pub async fn raid_disable(ctx: Context<'_>) -> Result<(), Error> {
+    if !crate::safeguards::allow_weakening(ctx, "disable raid detection").await? {
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let rec = sqlx::query!(
+        "DELETE FROM raid_config WHERE guild_id = $1 RETURNING to_jsonb(raid_config) AS \"config!\"",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string()
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or("Raid detection is not enabled")?;
+
+    let log = crate::audit::record(ctx, &mut tx, Some(rec.config), None).await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    crate::safeguards::record_deletion(ctx, "raid_config".to_string()).await?;
+
+    ctx.say("Raid detection disabled").await?;
+
+    Ok(())
+}
+
+/// Spam detection
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("spam_setup", "spam_view", "spam_disable")
+)]
+pub async fn spam(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Enable or update spam detection for the server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "setup")]
+pub async fn spam_setup(
+    ctx: Context<'_>,
+    #[description = "The time interval messages are compared in"]
+    spam_time: i64,
+    #[description = "The time unit for the time interval [seconds/minutes/hours/days]"]
+    spam_time_unit: crate::utils::Unit,
+    #[description = "Identical messages a user can send in the interval (0 to disable)"]
+    spam_duplicates: i32,
+    #[description = "Channels a user can post the same message to in the interval (0 to disable)"]
+    spam_channels: i32,
+    #[description = "Mentions a single message can contain (0 to disable)"]
+    spam_mentions: i32,
+    #[description = "Treat invite links as spam"]
+    spam_invites: bool,
+) -> Result<(), Error> {
+    if spam_duplicates < 0 || spam_channels < 0 || spam_mentions < 0 {
+        return Err("Thresholds cannot be negative".into());
+    }
+
+    let spam_time = spam_time * spam_time_unit.to_seconds();
+
+    if spam_time <= 0 {
+        return Err("The time interval must be positive".into());
+    }
+
+    if spam_time > crate::spam::MAX_TRACKED.as_secs() as i64 {
+        return Err(format!(
+            "Messages can only be compared over at most {:?}",
+            crate::spam::MAX_TRACKED
+        )
+        .into());
+    }
+
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    if let Some(current) = crate::core::SpamConfig::from_guild(&ctx.data().pool, guild_id).await? {
+        let new = crate::core::SpamConfig {
+            spam_time: sqlx::postgres::types::PgInterval {
+                months: 0,
+                days: 0,
+                microseconds: spam_time * 1_000_000,
+            },
+            spam_duplicates,
+            spam_channels,
+            spam_mentions,
+            spam_invites,
+        };
+
+        if current.weakened_by(&new)
+            && !crate::safeguards::allow_weakening(ctx, "weaken spam detection").await?
+        {
+            return Ok(());
+        }
+    }
+
+    let old = sqlx::query!(
+        "SELECT to_jsonb(spam_config) AS \"config!\" FROM spam_config WHERE guild_id = $1",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string()
+    )
+    .fetch_optional(&ctx.data().pool)
+    .await?;
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let rec = sqlx::query!(
+        "
+            INSERT INTO spam_config (
+                guild_id,
+                spam_time,
+                spam_duplicates,
+                spam_channels,
+                spam_mentions,
+                spam_invites
+            )
+            VALUES (
+                $1,
+                make_interval(secs => $2),
+                $3,
+                $4,
+                $5,
+                $6
+            )
+            ON CONFLICT (guild_id) DO UPDATE SET
+                spam_time = EXCLUDED.spam_time,
+                spam_duplicates = EXCLUDED.spam_duplicates,
+                spam_channels = EXCLUDED.spam_channels,
+                spam_mentions = EXCLUDED.spam_mentions,
+                spam_invites = EXCLUDED.spam_invites
+            RETURNING to_jsonb(spam_config) AS \"config!\"
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        spam_time as f64,
+        spam_duplicates,
+        spam_channels,
+        spam_mentions,
+        spam_invites
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let log = crate::audit::record(ctx, &mut tx, old.map(|o| o.config), Some(rec.config)).await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.data()
+        .spam_configs
+        .invalidate(ctx.guild_id().ok_or("Could not get guild id")?);
+
+    ctx.say("Spam detection setup successfully. Spam is recorded as ``Message Spam`` actions, use ``/limits add`` to decide what happens to spammers")
+        .await?;
+
+    Ok(())
+}
+
+/// View the spam detection settings for this server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "view")]
+pub async fn spam_view(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(spam_config) = crate::core::SpamConfig::from_guild(
+        &ctx.data().pool,
+        ctx.guild_id().ok_or("Could not get guild id")?,
+    )
+    .await?
+    else {
+        ctx.say("Spam detection is not enabled, use ``/spam setup`` to enable it!")
+            .await?;
+        return Ok(());
+    };
+
+    let embed = CreateEmbed::default()
+        .title("Spam Detection")
+        .description(format!(
+            "Messages are compared over {}",
+            crate::utils::parse_pg_interval(spam_config.spam_time)
+        ))
+        .field(
+            "Identical Messages",
+            spam_config.spam_duplicates.to_string(),
+            true,
+        )
+        .field(
+            "Channels Per Message",
+            spam_config.spam_channels.to_string(),
+            true,
+        )
+        .field(
+            "Mentions Per Message",
+            spam_config.spam_mentions.to_string(),
+            true,
+        )
+        .field("Invite Links", spam_config.spam_invites.to_string(), true)
+        .color(0x00ff00);
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Disable spam detection for the server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "disable")]
+pub async fn spam_disable(ctx: Context<'_>) -> Result<(), Error> {
+    if !crate::safeguards::allow_weakening(ctx, "disable spam detection").await? {
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let rec = sqlx::query!(
+        "DELETE FROM spam_config WHERE guild_id = $1 RETURNING to_jsonb(spam_config) AS \"config!\"",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string()
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or("Spam detection is not enabled")?;
+
+    let log = crate::audit::record(ctx, &mut tx, Some(rec.config), None).await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.data()
+        .spam_configs
+        .invalidate(ctx.guild_id().ok_or("Could not get guild id")?);
+
+    crate::safeguards::record_deletion(ctx, "spam_config".to_string()).await?;
+
+    ctx.say("Spam detection disabled").await?;
+
+    Ok(())
+}
+
+/// Webhooks for external alerting
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("webhooks_add", "webhooks_view", "webhooks_remove", "webhooks_test")
+)]
+pub async fn webhooks(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Post limit hits, punishments and Skynet's removal to a URL
+///
+/// Webhooks keep working after Skynet has been removed from the server, which makes them the
+/// only way to be alerted of a removal besides a DM to the owner. The removal itself cannot be
+/// punished, as Skynet can no longer act in the server
+// Slash command only, as prefix commands cannot reply with the secret ephemerally
+#[poise::command(slash_command, guild_only, rename = "add")]
+pub async fn webhooks_add(
+    ctx: Context<'_>,
+    #[description = "The https URL to post events to"] url: String,
+) -> Result<(), Error> {
+    crate::webhooks::resolve_url(&url).await?;
+
+    let secret = botox::crypto::gen_random(64);
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let rec = sqlx::query!(
+        "
+            INSERT INTO guild_webhooks (guild_id, url, secret)
+            VALUES ($1, $2, $3)
+            RETURNING webhook_id
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        url,
+        secret
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // The URL may contain a token, so only the host is recorded. The secret is left out too
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        None,
+        Some(json!({
+            "webhook_id": rec.webhook_id,
+            "host": crate::webhooks::url_host(&url),
+        })),
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "Added webhook ``{}``. Requests are signed with HMAC-SHA256 in the ``X-Skynet-Signature`` header using this secret, it will not be shown again:\n||{}||",
+                rec.webhook_id, secret
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// View the webhooks setup for this server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "view")]
+pub async fn webhooks_view(ctx: Context<'_>) -> Result<(), Error> {
+    let webhooks = crate::webhooks::Webhook::from_guild(
+        &ctx.data().pool,
+        ctx.guild_id().ok_or("Could not get guild id")?,
+    )
+    .await?;
+
+    if webhooks.is_empty() {
+        ctx.say("No webhooks setup for this server, use ``/webhooks add`` to add one!")
+            .await?;
+        return Ok(());
+    }
+
+    let mut embed = CreateEmbed::default().title("Webhooks").color(0x00ff00);
+
+    for webhook in webhooks {
+        // The URL may contain a token, so only show the host
+        embed = embed.field(
+            webhook.webhook_id,
+            crate::webhooks::url_host(&webhook.url),
+            false,
+        );
+    }
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Remove a webhook from the server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "remove")]
+pub async fn webhooks_remove(
+    ctx: Context<'_>,
+    #[description = "The webhook id to remove"] webhook_id: String,
+) -> Result<(), Error> {
+    if !crate::safeguards::allow_weakening(ctx, &format!("remove webhook {}", webhook_id)).await? {
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let rec = sqlx::query!(
+        "
+            DELETE FROM guild_webhooks
+            WHERE guild_id = $1
+            AND webhook_id = $2
+            RETURNING url
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        webhook_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or("Could not find webhook")?;
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(json!({
+            "webhook_id": webhook_id,
+            "host": crate::webhooks::url_host(&rec.url),
+        })),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    crate::safeguards::record_deletion(ctx, format!("webhook:{}", webhook_id)).await?;
+
+    ctx.say("Removed webhook successfully").await?;
+
+    Ok(())
+}
+
+/// Send a test event to a webhook
+#[poise::command(prefix_command, slash_command, guild_only, rename = "test")]
+pub async fn webhooks_test(
+    ctx: Context<'_>,
+    #[description = "The webhook id to test"] webhook_id: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    let webhook = crate::webhooks::Webhook::from_guild(&ctx.data().pool, guild_id)
+        .await?
+        .into_iter()
+        .find(|w| w.webhook_id == webhook_id)
+        .ok_or("Could not find webhook")?;
+
+    // Delivery may take a while with retries
+    ctx.defer().await?;
+
+    webhook
+        .deliver(
+            crate::webhooks::WebhookEvent::Test,
+            &crate::webhooks::test_body(guild_id)?,
+        )
+        .await
+        .map_err(|e| format!("Test event could not be delivered: {}", e))?;
+
+    ctx.say("Test event delivered successfully").await?;
+
+    Ok(())
+}
+
+/// Whitelist management, whitelisted users and roles are not counted towards limits
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("whitelist_add", "whitelist_view", "whitelist_remove")
+)]
+pub async fn whitelist(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Add a user or role to the whitelist
+#[poise::command(prefix_command, slash_command, guild_only, rename = "add")]
+pub async fn whitelist_add(
+    ctx: Context<'_>,
+    #[description = "The user to whitelist"] user: Option<User>,
+    #[description = "The role to whitelist"] role: Option<Role>,
+) -> Result<(), Error> {
+    let (target_id, target_type) = user_or_role(user, role)?;
+
+    let change = format!("whitelist {} {}", target_type, target_id);
+
+    if !crate::safeguards::allow_weakening(ctx, &change).await? {
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let res = sqlx::query!(
+        "
+            INSERT INTO guild_whitelist (guild_id, target_id, target_type)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        target_id,
+        target_type
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err("Already whitelisted".into());
+    }
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        None,
+        Some(json!({ "target_id": target_id, "target_type": target_type })),
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.say(format!(
+        "Whitelisted {}",
+        mention_target(&target_id, target_type)
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// View the whitelist
+#[poise::command(prefix_command, slash_command, guild_only, rename = "view")]
+pub async fn whitelist_view(ctx: Context<'_>) -> Result<(), Error> {
+    let whitelist = sqlx::query!(
+        "
+            SELECT target_id, target_type, created_at
+            FROM guild_whitelist
+            WHERE guild_id = $1
+            ORDER BY created_at
+        ",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string()
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
 
+    if whitelist.is_empty() {
+        ctx.say("The whitelist is empty").await?;
         return Ok(());
     }
 
-    let mut cr = CreateReply::default();
-    let mut embeds = vec![];
-    let mut added: i32 = 0;
-    let mut i = 0;
+    let entries = whitelist
+        .iter()
+        .map(|w| {
+            format!(
+                "{} (added <t:{}:R>)",
+                mention_target(&w.target_id, &w.target_type),
+                w.created_at.timestamp()
+            )
+        })
+        .collect::<Vec<_>>();
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::new()
+                .title("Whitelist")
+                .description(entries.join("\n"))
+                .color(0x00ff00),
+        ),
+    )
+    .await?;
 
-    for action in actions {
-        added += 1;
+    Ok(())
+}
 
-        if added >= 8 {
-            added = 0;
-            i += 1;
-        }
+/// Remove a user or role from the whitelist
+#[poise::command(prefix_command, slash_command, guild_only, rename = "remove")]
+pub async fn whitelist_remove(
+    ctx: Context<'_>,
+    #[description = "The user to remove"] user: Option<User>,
+    #[description = "The role to remove"] role: Option<Role>,
+) -> Result<(), Error> {
+    let (target_id, target_type) = user_or_role(user, role)?;
 
-        if embeds.len() <= i {
-            embeds.push(CreateEmbed::default().title("Actions").color(0x00ff00));
-        }
+    let mut tx = ctx.data().pool.begin().await?;
+
+    let res = sqlx::query!(
+        "DELETE FROM guild_whitelist WHERE guild_id = $1 AND target_id = $2",
+        ctx.guild_id().ok_or("Could not get guild id")?.to_string(),
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err("Not whitelisted?".into());
+    }
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(json!({ "target_id": target_id, "target_type": target_type })),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.say("Removed from whitelist").await?;
+
+    Ok(())
+}
+
+/// Lockdown management
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("lockdown_start", "lockdown_end")
+)]
+pub async fn lockdown(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Stop members from sending messages and creating invites
+#[poise::command(prefix_command, slash_command, guild_only, rename = "start")]
+pub async fn lockdown_start(
+    ctx: Context<'_>,
+    #[description = "Also pause all invites to the server"]
+    pause_invites: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    let started = crate::lockdown::start_lockdown(
+        ctx.serenity_context(),
+        &ctx.data().pool,
+        guild_id,
+        pause_invites.unwrap_or_default(),
+        &format!("Lockdown started by {}", ctx.author().name),
+    )
+    .await?;
+
+    if !started {
+        return Err("Server is already in lockdown".into());
+    }
+
+    ctx.say("Server is now in lockdown, use ``/lockdown end`` to end it")
+        .await?;
+
+    crate::utils::send_log(
+        ctx.serenity_context(),
+        &ctx.data().pool,
+        guild_id,
+        CreateEmbed::default()
+            .title("Lockdown Started")
+            .description(format!("Lockdown started by {}", ctx.author().mention()))
+            .color(0xff0000),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// End a lockdown, restoring the previous permissions
+#[poise::command(prefix_command, slash_command, guild_only, rename = "end")]
+pub async fn lockdown_end(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    let ended = crate::lockdown::end_lockdown(
+        ctx.serenity_context(),
+        &ctx.data().pool,
+        guild_id,
+        &format!("Lockdown ended by {}", ctx.author().name),
+    )
+    .await?;
+
+    if !ended {
+        return Err("Server is not in lockdown".into());
+    }
+
+    ctx.say("Lockdown ended, permissions have been restored").await?;
+
+    crate::utils::send_log(
+        ctx.serenity_context(),
+        &ctx.data().pool,
+        guild_id,
+        CreateEmbed::default()
+            .title("Lockdown Ended")
+            .description(format!("Lockdown ended by {}", ctx.author().mention()))
+            .color(0x00ff00),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Action management
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("actions_view", "actions_joins")
+)]
+pub async fn actions(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Actions shown on each page of /actions
+const ACTIONS_PER_PAGE: i64 = 8;
+
+/// How long a paginated reply keeps responding to its buttons
+const PAGE_TIMEOUT_SECS: u64 = 120;
+
+/// View actions taken by users that have been recorded by SkyNet
+#[poise::command(prefix_command, slash_command, guild_only, rename = "view")]
+pub async fn actions_view(
+    ctx: Context<'_>,
+    #[description = "User ID (optional)"] user_id: Option<UserId>,
+    #[description = "Only show actions of this type"]
+    limit_type: Option<crate::core::UserLimitTypesChoices>,
+    #[description = "Only show actions on or after this date (YYYY-MM-DD)"]
+    after: Option<String>,
+    #[description = "Only show actions before this date (YYYY-MM-DD)"]
+    before: Option<String>,
+    #[description = "Only show actions on this target ID"]
+    target: Option<String>,
+    #[description = "Only show actions that contributed to a hit limit"]
+    hits_only: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    let filter = crate::core::ActionFilter {
+        user_id,
+        limit_type: limit_type.map(|t| t.resolve()),
+        after: after.as_deref().map(crate::utils::parse_date).transpose()?,
+        before: before
+            .as_deref()
+            .map(crate::utils::parse_date)
+            .transpose()?,
+        target,
+        hits_only: hits_only.unwrap_or(false),
+    };
+
+    let total = crate::core::Action::count(&ctx.data().pool, guild_id, &filter).await?;
+
+    if total == 0 {
+        ctx.say("No actions recorded").await?;
+        return Ok(());
+    }
+
+    let pages = (total + ACTIONS_PER_PAGE - 1) / ACTIONS_PER_PAGE;
+    let mut page = 0;
+
+    let reply = actions_page(ctx, guild_id, &filter, page, pages, total).await?;
+    let handle = ctx.send(reply).await?;
+    let msg_id = handle.message().await?.id;
+
+    while let Some(interaction) =
+        crate::utils::next_component(ctx, msg_id, PAGE_TIMEOUT_SECS).await?
+    {
+        page = crate::utils::turn_page(interaction.data.custom_id.as_str(), page, pages);
+
+        let reply = actions_page(ctx, guild_id, &filter, page, pages, total).await?;
+        handle.edit(ctx, reply).await?;
+    }
+
+    // Stop showing buttons that no longer do anything
+    let reply = actions_page(ctx, guild_id, &filter, page, pages, total).await?;
+    handle.edit(ctx, reply.components(vec![])).await?;
+
+    Ok(())
+}
+
+async fn actions_page(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    filter: &crate::core::ActionFilter,
+    page: i64,
+    pages: i64,
+    total: i64,
+) -> Result<CreateReply<'static>, Error> {
+    let actions = crate::core::Action::page(
+        &ctx.data().pool,
+        guild_id,
+        filter,
+        ACTIONS_PER_PAGE,
+        page * ACTIONS_PER_PAGE,
+    )
+    .await?;
+
+    let mut embed = CreateEmbed::default()
+        .title("Actions")
+        .footer(CreateEmbedFooter::new(format!(
+            "{} actions in total",
+            total
+        )))
+        .color(0x00ff00);
 
-        embeds[i] = embeds[i].clone().field(
+    for action in actions {
+        embed = embed.field(
             action.action_id.clone(),
             format!(
                 "``{limit_type}`` on ``{action_target}`` by {user_id} at <t:{timestamp}:R> [{id}]\n**Hit Limits:** {limits_hit:#?}",
@@ -364,100 +2511,467 @@ pub async fn actions_view(
         );
     }
 
-    for embed in embeds {
-        cr = cr.embed(embed);
+    Ok(CreateReply::default()
+        .embed(embed)
+        .components(vec![crate::utils::page_buttons(page, pages)]))
+}
+
+/// View actions taken on members that matched a join rule
+#[poise::command(prefix_command, slash_command, guild_only, rename = "joins")]
+pub async fn actions_joins(
+    ctx: Context<'_>,
+    #[description = "User ID (optional)"] user_id: Option<UserId>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    let total = crate::core::JoinAction::count(&ctx.data().pool, guild_id, user_id).await?;
+
+    if total == 0 {
+        ctx.say("No join actions recorded").await?;
+        return Ok(());
     }
 
-    ctx.send(cr).await?;
+    let pages = (total + ACTIONS_PER_PAGE - 1) / ACTIONS_PER_PAGE;
+    let mut page = 0;
+
+    let reply = join_actions_page(ctx, guild_id, user_id, page, pages).await?;
+    let handle = ctx.send(reply).await?;
+    let msg_id = handle.message().await?.id;
+
+    while let Some(interaction) =
+        crate::utils::next_component(ctx, msg_id, PAGE_TIMEOUT_SECS).await?
+    {
+        page = crate::utils::turn_page(interaction.data.custom_id.as_str(), page, pages);
+
+        let reply = join_actions_page(ctx, guild_id, user_id, page, pages).await?;
+        handle.edit(ctx, reply).await?;
+    }
+
+    let reply = join_actions_page(ctx, guild_id, user_id, page, pages).await?;
+    handle.edit(ctx, reply.components(vec![])).await?;
 
     Ok(())
 }
 
-/// View hit limits
-#[poise::command(prefix_command, slash_command, guild_only, rename = "hit")]
-pub async fn hit_limits(
+async fn join_actions_page(
     ctx: Context<'_>,
-) -> Result<(), Error> {
-    let hit_limits = crate::core::PastHitLimits::guild(
+    guild_id: GuildId,
+    user_id: Option<UserId>,
+    page: i64,
+    pages: i64,
+) -> Result<CreateReply<'static>, Error> {
+    let actions = crate::core::JoinAction::page(
         &ctx.data().pool,
-        ctx.guild_id().ok_or("Could not get guild id")?,
-    ).await?;        
+        guild_id,
+        user_id,
+        ACTIONS_PER_PAGE,
+        page * ACTIONS_PER_PAGE,
+    )
+    .await?;
 
-    if hit_limits.is_empty() {
-        ctx.say("No hit limits recorded").await?;
-        return Ok(());
+    let mut embed = CreateEmbed::default().title("Join Actions").color(0x00ff00);
+
+    for action in actions {
+        embed = embed.field(
+            action.action_id.clone(),
+            format!(
+                "``{rule_action}`` on {user_id} at <t:{timestamp}:R> due to {rule} [{id}]\n**Notes:** {notes}",
+                rule_action = action.rule_action.to_cond(),
+                user_id = action.user_id.mention().to_string() + " (" + &action.user_id.to_string() + ")",
+                timestamp = action.created_at.timestamp(),
+                rule = match action.rule_id {
+                    Some(ref rule_id) => format!("rule ``{}``", rule_id),
+                    None => "a removed rule".to_string(),
+                },
+                id = action.action_id,
+                notes = action.notes.join(", ")
+            ),
+            false,
+        );
     }
 
-    if hit_limits.len() > 30 {
-        let hit_limits = serde_json::to_string(&hit_limits).map_err(|_| "Could not serialize hit_limits")?;
+    Ok(CreateReply::default()
+        .embed(embed)
+        .components(vec![crate::utils::page_buttons(page, pages)]))
+}
 
-        // Create a attachment
-        let attachment = CreateAttachment::bytes(hit_limits.into_bytes(), "hit_limits.json");
+/// User lookups
+#[poise::command(prefix_command, slash_command, guild_only, subcommands("user_profile"))]
+pub async fn user(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
 
-        ctx.send(CreateReply::default().attachment(attachment)).await?;
+/// View everything Skynet knows about a user in this server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "profile")]
+pub async fn user_profile(
+    ctx: Context<'_>,
+    #[description = "The user to view"] user: User,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
 
-        return Ok(());
+    // Action counts over the last 24 hours, 7 days and 30 days
+    let by_type = sqlx::query!(
+        "
+            SELECT limit_type,
+            COUNT(*) FILTER (WHERE created_at > NOW() - INTERVAL '1 day') AS \"day!\",
+            COUNT(*) FILTER (WHERE created_at > NOW() - INTERVAL '7 days') AS \"week!\",
+            COUNT(*) AS \"month!\"
+            FROM user_actions
+            WHERE guild_id = $1
+            AND user_id = $2
+            AND created_at > NOW() - INTERVAL '30 days'
+            GROUP BY limit_type
+        ",
+        guild_id.to_string(),
+        user.id.to_string()
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    let mut counts = String::new();
+
+    for variant in crate::core::UserLimitTypes::VARIANTS {
+        let Some(row) = by_type.iter().find(|r| r.limit_type == *variant) else {
+            continue;
+        };
+
+        let Ok(limit_type) = variant.parse::<crate::core::UserLimitTypes>() else {
+            continue;
+        };
+
+        counts.push_str(&format!(
+            "{:<28}{:>5}{:>5}{:>5}\n",
+            limit_type.to_cond(),
+            row.day,
+            row.week,
+            row.month
+        ));
     }
 
-    let mut cr = CreateReply::default();
-    let mut embeds = vec![];
-    let mut added: i32 = 0;
-    let mut i = 0;
+    let counts = if counts.is_empty() {
+        "No actions in the last 30 days".to_string()
+    } else {
+        format!(
+            "```\n{:<28}{:>5}{:>5}{:>5}\n{}```",
+            "", "24h", "7d", "30d", counts
+        )
+    };
 
-    for hit_limit in hit_limits {
-        added += 1;
+    let hit_count =
+        crate::core::PastHitLimits::count(&ctx.data().pool, guild_id, Some(user.id), None).await?;
 
-        if added >= 8 {
-            added = 0;
-            i += 1;
-        }
+    let recent_hits =
+        crate::core::PastHitLimits::page(&ctx.data().pool, guild_id, Some(user.id), None, 5, 0)
+            .await?;
 
-        if embeds.len() <= i {
-            embeds.push(CreateEmbed::default().title("Past Limits History").color(0x00ff00));
+    let mut hits = recent_hits
+        .iter()
+        .map(|h| {
+            format!(
+                "``{}`` <t:{}:R>, punishment {} [{}]",
+                h.limit_name,
+                h.created_at.timestamp(),
+                if h.punishment_failed {
+                    "failed"
+                } else {
+                    "applied"
+                },
+                h.id
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if hits.is_empty() {
+        hits = "None".to_string();
+    } else if hit_count > recent_hits.len() as i64 {
+        hits.push_str(&format!(
+            "\n...and {} more, see ``/limits hit``",
+            hit_count - recent_hits.len() as i64
+        ));
+    }
+
+    let is_admin = crate::utils::is_guild_admin(
+        ctx.serenity_context(),
+        &ctx.data().pool,
+        guild_id,
+        user.id.to_string(),
+    )
+    .await
+    .is_ok();
+
+    // Members who left cannot be checked for whitelisted roles
+    let is_whitelisted =
+        crate::utils::is_whitelisted(ctx.serenity_context(), &ctx.data().pool, guild_id, user.id)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(
+                    "Failed to check whitelist of {} in {}: {}",
+                    user.id, guild_id, e
+                );
+                false
+            });
+
+    let quarantine_role = crate::utils::quarantine_role(&ctx.data().pool, guild_id).await?;
+
+    // What the user is currently subject to, as far as Skynet can tell
+    let state = match guild_id.member(ctx.serenity_context(), user.id).await {
+        Ok(member) => {
+            let mut state = Vec::new();
+
+            if let Some(until) = member.communication_disabled_until {
+                if until.unix_timestamp() > sqlx::types::chrono::Utc::now().timestamp() {
+                    state.push(format!("Timed out until <t:{}:f>", until.unix_timestamp()));
+                }
+            }
+
+            if let Some(role_id) = quarantine_role {
+                if member.roles.contains(&role_id) {
+                    state.push("Quarantined".to_string());
+                }
+            }
+
+            if state.is_empty() {
+                "In the server, not punished".to_string()
+            } else {
+                state.join("\n")
+            }
         }
+        Err(_) => "Not in the server, they may have been kicked or banned".to_string(),
+    };
+
+    let embed = CreateEmbed::default()
+        .title(format!("Profile of {}", user.name))
+        .description(format!("{} ({})", user.mention(), user.id))
+        .thumbnail(user.face())
+        .field("Admin", if is_admin { "Yes" } else { "No" }, true)
+        .field(
+            "Whitelisted",
+            if is_whitelisted { "Yes" } else { "No" },
+            true,
+        )
+        .field("Hit Limits", hit_count.to_string(), true)
+        .field("Actions", counts, false)
+        .field("Recent Hits", hits, false)
+        .field("Current State", state, false)
+        .color(if hit_count > 0 { 0xff0000 } else { 0x00ff00 });
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Hit limits shown on each page of /limits hit
+const HITS_PER_PAGE: i64 = 5;
+
+/// View hit limits
+#[poise::command(prefix_command, slash_command, guild_only, rename = "hit")]
+pub async fn hit_limits(
+    ctx: Context<'_>,
+    #[description = "Only show hits of this user"] user_id: Option<UserId>,
+    #[description = "Only show hits of this limit"]
+    #[autocomplete = "crate::autocompletes::limits_autocomplete"]
+    limit_id: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    let total =
+        crate::core::PastHitLimits::count(&ctx.data().pool, guild_id, user_id, limit_id.as_deref())
+            .await?;
+
+    if total == 0 {
+        ctx.say("No hit limits recorded").await?;
+        return Ok(());
+    }
+
+    let pages = (total + HITS_PER_PAGE - 1) / HITS_PER_PAGE;
+    let mut page = 0;
 
-        let mut notes = String::new();
+    let mut hits = crate::core::PastHitLimits::page(
+        &ctx.data().pool,
+        guild_id,
+        user_id,
+        limit_id.as_deref(),
+        HITS_PER_PAGE,
+        0,
+    )
+    .await?;
 
-        for note in hit_limit.notes {
-            notes.push_str(&format!("- ``{}`` ", note));
+    let handle = ctx.send(hits_page(&hits, page, pages, total)).await?;
+    let msg_id = handle.message().await?.id;
+
+    while let Some(interaction) =
+        crate::utils::next_component(ctx, msg_id, PAGE_TIMEOUT_SECS).await?
+    {
+        // Opening a single hit
+        if let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind {
+            if let Some(hit) = values
+                .first()
+                .and_then(|v| hits.iter().find(|h| h.id.as_str() == v.as_str()))
+            {
+                handle.edit(ctx, hit_detail(hit)).await?;
+            }
+
+            continue;
         }
 
-        let mut causes = String::new();
-
-        for cause in hit_limit.cause {
-            causes.push_str(
-                &format!(
-                    "``{limit_type}`` on ``{action_target}`` by {user_id} at <t:{timestamp}:R> [{id}]\n**Hit Limits:** {limits_hit:#?}",
-                    limit_type = cause.limit_type,
-                    action_target = cause.action_target,
-                    user_id = cause.user_id.mention().to_string() + " (" + &cause.user_id.to_string() + ")",
-                    timestamp = cause.created_at.timestamp(),
-                    id = cause.action_id,
-                    limits_hit = cause.limits_hit
-                ),
-            );
+        let new_page = crate::utils::turn_page(interaction.data.custom_id.as_str(), page, pages);
+
+        if new_page != page {
+            page = new_page;
+
+            hits = crate::core::PastHitLimits::page(
+                &ctx.data().pool,
+                guild_id,
+                user_id,
+                limit_id.as_deref(),
+                HITS_PER_PAGE,
+                page * HITS_PER_PAGE,
+            )
+            .await?;
         }
 
-        embeds[i] = embeds[i].clone().field(
-            hit_limit.id.clone(),
+        // Page buttons and the back button both show the list again
+        handle
+            .edit(ctx, hits_page(&hits, page, pages, total))
+            .await?;
+    }
+
+    handle
+        .edit(ctx, hits_page(&hits, page, pages, total).components(vec![]))
+        .await?;
+
+    Ok(())
+}
+
+fn hits_page(
+    hits: &[crate::core::PastHitLimits],
+    page: i64,
+    pages: i64,
+    total: i64,
+) -> CreateReply<'static> {
+    let mut embed = CreateEmbed::default()
+        .title("Past Limits History")
+        .footer(CreateEmbedFooter::new(format!(
+            "{} hits in total, select one below for details",
+            total
+        )))
+        .color(0x00ff00);
+
+    let mut options = Vec::new();
+
+    for hit in hits {
+        embed = embed.field(
+            hit.id.clone(),
             format!(
-                "Limit ``{limit_id}`` reached by ``{user_id}`` at <t:{timestamp}:R> [{id}]\n**Notes:** {notes}\n**Causes:** {causes}",
-                limit_id = hit_limit.limit_id,
-                user_id = hit_limit.user_id.mention().to_string() + " (" + &hit_limit.user_id.to_string() + ")",
-                timestamp = hit_limit.created_at.timestamp(),
-                id = hit_limit.id,
-                notes = notes,
-                causes = causes
+                "Limit ``{limit_name}`` reached by {user_id} at <t:{timestamp}:R>\n**Causes:** {causes}, **Punishment:** {punishment}",
+                limit_name = hit.limit_name,
+                user_id = hit.user_id.mention().to_string() + " (" + &hit.user_id.to_string() + ")",
+                timestamp = hit.created_at.timestamp(),
+                causes = hit.cause.len(),
+                punishment = if hit.punishment_failed { "Failed" } else { "Applied" }
             ),
             false,
         );
+
+        options.push(
+            CreateSelectMenuOption::new(
+                format!("{} by {}", hit.limit_name, hit.user_id)
+                    .chars()
+                    .take(100)
+                    .collect::<String>(),
+                hit.id.clone(),
+            )
+            .description(hit.created_at.format("%Y-%m-%d %H:%M UTC").to_string()),
+        );
     }
 
-    for embed in embeds {
-        cr = cr.embed(embed);
+    CreateReply::default().embed(embed).components(vec![
+        CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+                "hit_select",
+                CreateSelectMenuKind::String {
+                    options: options.into(),
+                },
+            )
+            .placeholder("View a hit"),
+        ),
+        crate::utils::page_buttons(page, pages),
+    ])
+}
+
+fn hit_detail(hit: &crate::core::PastHitLimits) -> CreateReply<'static> {
+    let mut causes = String::new();
+
+    for cause in hit.cause.iter() {
+        causes.push_str(&format!(
+            "- ``{limit_type}`` on ``{action_target}`` at <t:{timestamp}:R> [{id}]\n",
+            limit_type = cause.limit_type,
+            action_target = cause.action_target,
+            timestamp = cause.created_at.timestamp(),
+            id = cause.action_id,
+        ));
     }
 
-    ctx.send(cr).await?;
+    // Causes that have since been deleted are not returned
+    if causes.is_empty() {
+        causes = "No recorded causes".to_string();
+    } else if causes.chars().count() > 1024 {
+        causes = causes.chars().take(1020).collect::<String>() + "...";
+    }
 
-    Ok(())
-}
\ No newline at end of file
+    let mut notes = String::new();
+
+    for note in hit.notes.iter() {
+        notes.push_str(&format!("- ``{}``\n", note));
+    }
+
+    // Notes include punishment errors, which can be long
+    if notes.is_empty() {
+        notes = "None".to_string();
+    } else if notes.chars().count() > 1024 {
+        notes = notes.chars().take(1020).collect::<String>() + "...";
+    }
+
+    let embed = CreateEmbed::default()
+        .title(format!("Hit {}", hit.id))
+        .field(
+            "Limit",
+            format!("{} [{}]", hit.limit_name, hit.limit_id),
+            true,
+        )
+        .field(
+            "User",
+            hit.user_id.mention().to_string() + " (" + &hit.user_id.to_string() + ")",
+            true,
+        )
+        .field(
+            "When",
+            format!("<t:{}:F>", hit.created_at.timestamp()),
+            true,
+        )
+        .field(
+            "Punishment",
+            if hit.punishment_failed {
+                "Failed"
+            } else {
+                "Applied"
+            },
+            true,
+        )
+        .field("Causes", causes, false)
+        .field("Notes", notes, false)
+        .color(if hit.punishment_failed {
+            0xff0000
+        } else {
+            0x00ff00
+        });
+
+    CreateReply::default()
+        .embed(embed)
+        .components(vec![CreateActionRow::Buttons(vec![CreateButton::new(
+            "hit_back",
+        )
+        .label("Back")
+        .style(ButtonStyle::Secondary)])])
+}
@@ -17,6 +17,9 @@ pub struct Config {
     pub owners: Vec<UserId>,
     pub client_id: String,
     pub client_secret: String,
+    /// Bearer token required to scrape /metrics, which is disabled if unset
+    #[serde(default)]
+    pub metrics_token: Option<String>,
 }
 
 impl Default for Config {
@@ -32,6 +35,7 @@ impl Default for Config {
                 UserId::new(728871946456137770),
                 UserId::new(510065483693817867),
             ],
+            metrics_token: None,
         }
     }
 }
@@ -1,9 +1,9 @@
-use poise::serenity_prelude::{GuildId, UserId};
+use poise::serenity_prelude::{GuildId, Permissions, UserId};
 use serde::Serialize;
 use sqlx::{
     postgres::types::PgInterval,
     types::chrono::{DateTime, Utc},
-    PgPool,
+    PgConnection, PgPool,
 };
 use strum_macros::{Display, EnumString, EnumVariantNames};
 
@@ -29,6 +29,16 @@ pub enum UserLimitTypesChoices {
     Ban,
     #[name = "Unban"]
     Unban,
+    #[name = "Message Spam"]
+    MessageSpam,
+    #[name = "Mass Mention"]
+    MassMention,
+    #[name = "Skynet Tampering"]
+    BotTamper,
+    #[name = "Skynet Config Deletion"]
+    ConfigDelete,
+    #[name = "Threat Score"]
+    ThreatScore,
 }
 
 impl UserLimitTypesChoices {
@@ -43,6 +53,11 @@ impl UserLimitTypesChoices {
             Self::Kick => UserLimitTypes::Kick,
             Self::Ban => UserLimitTypes::Ban,
             Self::Unban => UserLimitTypes::Unban,
+            Self::MessageSpam => UserLimitTypes::MessageSpam,
+            Self::MassMention => UserLimitTypes::MassMention,
+            Self::BotTamper => UserLimitTypes::BotTamper,
+            Self::ConfigDelete => UserLimitTypes::ConfigDelete,
+            Self::ThreatScore => UserLimitTypes::ThreatScore,
         }
     }
 }
@@ -59,6 +74,11 @@ pub enum UserLimitTypes {
     Kick,
     Ban,
     Unban,
+    MessageSpam,  // Target is channel_id/message_id
+    MassMention,  // One action per mention, target is channel_id/message_id
+    BotTamper,    // Changes to Skynets own roles, always an immediate hit
+    ConfigDelete, // Removal of Skynet limits, join rules, raid/spam detection or webhooks
+    ThreatScore,  // Weighted sum of all other action types, never recorded as an action
 }
 
 impl UserLimitTypes {
@@ -73,8 +93,18 @@ impl UserLimitTypes {
             Self::Kick => "Kicks".to_string(),
             Self::Ban => "Bans".to_string(),
             Self::Unban => "Unbans".to_string(),
+            Self::MessageSpam => "Spam Messages".to_string(),
+            Self::MassMention => "Mentions".to_string(),
+            Self::BotTamper => "Skynet Tampering".to_string(),
+            Self::ConfigDelete => "Skynet Config Deletions".to_string(),
+            Self::ThreatScore => "Threat Score".to_string(),
         }
     }
+
+    /// Returns true if actions of this type target a message that should be deleted once a limit is hit
+    pub fn is_message(&self) -> bool {
+        matches!(self, Self::MessageSpam | Self::MassMention)
+    }
 }
 
 #[derive(poise::ChoiceParameter)]
@@ -85,6 +115,8 @@ pub enum UserLimitActionsChoices {
     KickUser,
     #[name = "Ban User"]
     BanUser,
+    #[name = "Lockdown Server"]
+    Lockdown,
 }
 
 impl UserLimitActionsChoices {
@@ -93,6 +125,7 @@ impl UserLimitActionsChoices {
             Self::RemoveAllRoles => UserLimitActions::RemoveAllRoles,
             Self::KickUser => UserLimitActions::KickUser,
             Self::BanUser => UserLimitActions::BanUser,
+            Self::Lockdown => UserLimitActions::Lockdown,
         }
     }
 }
@@ -103,6 +136,7 @@ pub enum UserLimitActions {
     RemoveAllRoles,
     KickUser,
     BanUser,
+    Lockdown,
 }
 
 impl UserLimitActions {
@@ -111,10 +145,267 @@ impl UserLimitActions {
             Self::RemoveAllRoles => "Remove All Roles".to_string(),
             Self::KickUser => "Kick User".to_string(),
             Self::BanUser => "Ban User".to_string(),
+            Self::Lockdown => "Lockdown Server".to_string(),
+        }
+    }
+
+    /// How urgently guild admins should be told about this action being taken
+    pub fn severity(&self) -> Severity {
+        match &self {
+            Self::RemoveAllRoles => Severity::Medium,
+            Self::KickUser => Severity::High,
+            Self::BanUser => Severity::Critical,
+            Self::Lockdown => Severity::High,
+        }
+    }
+
+    /// Permissions Skynet needs to take this action
+    pub fn required_permissions(&self) -> Permissions {
+        match &self {
+            Self::RemoveAllRoles => Permissions::MANAGE_ROLES,
+            Self::KickUser => Permissions::KICK_MEMBERS,
+            Self::BanUser => Permissions::BAN_MEMBERS,
+            Self::Lockdown => Permissions::MANAGE_ROLES,
+        }
+    }
+}
+
+#[derive(poise::ChoiceParameter)]
+pub enum SeverityChoices {
+    #[name = "Low"]
+    Low,
+    #[name = "Medium"]
+    Medium,
+    #[name = "High"]
+    High,
+    #[name = "Critical"]
+    Critical,
+}
+
+impl SeverityChoices {
+    pub fn resolve(self) -> Severity {
+        match self {
+            Self::Low => Severity::Low,
+            Self::Medium => Severity::Medium,
+            Self::High => Severity::High,
+            Self::Critical => Severity::Critical,
+        }
+    }
+}
+
+/// Severities are ordered from least to most severe
+#[derive(EnumString, Display, PartialEq, PartialOrd, EnumVariantNames, Clone, Debug, Serialize)]
+#[strum(serialize_all = "snake_case")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub fn to_cond(&self) -> String {
+        match &self {
+            Self::Low => "Low".to_string(),
+            Self::Medium => "Medium".to_string(),
+            Self::High => "High".to_string(),
+            Self::Critical => "Critical".to_string(),
+        }
+    }
+}
+
+#[derive(poise::ChoiceParameter)]
+pub enum RaidActionsChoices {
+    #[name = "Do Nothing"]
+    None,
+    #[name = "Kick Joiners"]
+    KickJoiners,
+    #[name = "Ban Joiners"]
+    BanJoiners,
+}
+
+impl RaidActionsChoices {
+    pub fn resolve(self) -> RaidActions {
+        match self {
+            Self::None => RaidActions::None,
+            Self::KickJoiners => RaidActions::KickJoiners,
+            Self::BanJoiners => RaidActions::BanJoiners,
+        }
+    }
+}
+
+/// Ordered from least to most severe
+#[derive(EnumString, Display, PartialEq, PartialOrd, EnumVariantNames, Clone, Debug, Serialize)]
+#[strum(serialize_all = "snake_case")]
+pub enum RaidActions {
+    None,
+    KickJoiners,
+    BanJoiners,
+}
+
+impl RaidActions {
+    pub fn to_cond(&self) -> String {
+        match &self {
+            Self::None => "Do Nothing".to_string(),
+            Self::KickJoiners => "Kick Joiners".to_string(),
+            Self::BanJoiners => "Ban Joiners".to_string(),
         }
     }
 }
 
+#[derive(poise::ChoiceParameter)]
+pub enum JoinRuleActionsChoices {
+    #[name = "Kick User"]
+    KickUser,
+    #[name = "Timeout User"]
+    TimeoutUser,
+    #[name = "Quarantine User"]
+    QuarantineUser,
+}
+
+impl JoinRuleActionsChoices {
+    pub fn resolve(self) -> JoinRuleActions {
+        match self {
+            Self::KickUser => JoinRuleActions::KickUser,
+            Self::TimeoutUser => JoinRuleActions::TimeoutUser,
+            Self::QuarantineUser => JoinRuleActions::QuarantineUser,
+        }
+    }
+}
+
+#[derive(EnumString, Display, PartialEq, EnumVariantNames, Clone, Debug, Serialize)]
+#[strum(serialize_all = "snake_case")]
+pub enum JoinRuleActions {
+    KickUser,
+    TimeoutUser,
+    QuarantineUser,
+}
+
+impl JoinRuleActions {
+    pub fn to_cond(&self) -> String {
+        match &self {
+            Self::KickUser => "Kick User".to_string(),
+            Self::TimeoutUser => "Timeout User".to_string(),
+            Self::QuarantineUser => "Quarantine User".to_string(),
+        }
+    }
+}
+
+#[derive(poise::ChoiceParameter)]
+pub enum SafeguardModeChoices {
+    #[name = "Cooldown"]
+    Cooldown,
+    #[name = "Owner Confirmation"]
+    OwnerConfirmation,
+}
+
+impl SafeguardModeChoices {
+    pub fn resolve(self) -> SafeguardMode {
+        match self {
+            Self::Cooldown => SafeguardMode::Cooldown,
+            Self::OwnerConfirmation => SafeguardMode::OwnerConfirmation,
+        }
+    }
+}
+
+/// How changes that weaken protection are held back when not made by the owner
+#[derive(EnumString, Display, PartialEq, EnumVariantNames, Clone, Debug, Serialize)]
+#[strum(serialize_all = "snake_case")]
+pub enum SafeguardMode {
+    Cooldown,
+    OwnerConfirmation,
+}
+
+impl SafeguardMode {
+    pub fn to_cond(&self) -> String {
+        match &self {
+            Self::Cooldown => "Cooldown".to_string(),
+            Self::OwnerConfirmation => "Owner Confirmation".to_string(),
+        }
+    }
+}
+
+#[derive(poise::ChoiceParameter)]
+pub enum CapabilityChoices {
+    #[name = "View Actions"]
+    ViewActions,
+    #[name = "Manage Limits"]
+    ManageLimits,
+    #[name = "Manage Whitelist"]
+    ManageWhitelist,
+    #[name = "Manage Admins"]
+    ManageAdmins,
+    #[name = "Restore Punishments"]
+    RestorePunishments,
+}
+
+impl CapabilityChoices {
+    pub fn resolve(self) -> Capability {
+        match self {
+            Self::ViewActions => Capability::ViewActions,
+            Self::ManageLimits => Capability::ManageLimits,
+            Self::ManageWhitelist => Capability::ManageWhitelist,
+            Self::ManageAdmins => Capability::ManageAdmins,
+            Self::RestorePunishments => Capability::RestorePunishments,
+        }
+    }
+}
+
+/// Capabilities can be granted to users or roles that are not full guild admins
+#[derive(EnumString, Display, PartialEq, EnumVariantNames, Clone, Debug, Serialize)]
+#[strum(serialize_all = "snake_case")]
+pub enum Capability {
+    ViewActions,
+    ManageLimits,
+    ManageWhitelist,
+    ManageAdmins,
+    RestorePunishments,
+}
+
+impl Capability {
+    pub fn to_cond(&self) -> String {
+        match &self {
+            Self::ViewActions => "View Actions".to_string(),
+            Self::ManageLimits => "Manage Limits".to_string(),
+            Self::ManageWhitelist => "Manage Whitelist".to_string(),
+            Self::ManageAdmins => "Manage Admins".to_string(),
+            Self::RestorePunishments => "Restore Punishments".to_string(),
+        }
+    }
+
+    /// The capability that allows using a command, or None if the command needs full admin
+    pub fn required_for(qualified_name: &str) -> Option<Self> {
+        match qualified_name {
+            "actions view" | "actions joins" | "limits view" | "limits weights" | "limits hit"
+            | "raid view" | "spam view" | "joinrules view" | "audit view" | "user profile"
+            | "guildstats" | "health" => Some(Self::ViewActions),
+            "limits add" | "limits remove" | "limits protect" | "limits unprotect"
+            | "limits weight" | "limits preset" => Some(Self::ManageLimits),
+            "whitelist add" | "whitelist remove" | "whitelist view" => Some(Self::ManageWhitelist),
+            "perms admins" | "perms grant" | "perms revoke" | "perms capabilities" => {
+                Some(Self::ManageAdmins)
+            }
+            "lockdown end" => Some(Self::RestorePunishments),
+            _ => None,
+        }
+    }
+}
+
+/// Narrows down which actions are fetched, unset fields match everything
+#[derive(Default)]
+pub struct ActionFilter {
+    pub user_id: Option<UserId>,
+    pub limit_type: Option<UserLimitTypes>,
+    /// Only actions at or after this time
+    pub after: Option<DateTime<Utc>>,
+    /// Only actions before this time
+    pub before: Option<DateTime<Utc>>,
+    /// Channel or role, message actions match the channel they were sent in
+    pub target: Option<String>,
+    /// Only actions that contributed to a hit limit
+    pub hits_only: bool,
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct Action {
     pub action_id: String,
@@ -127,54 +418,51 @@ pub struct Action {
 }
 
 impl Action {
-    /// Fetch actions for a action id
-    pub async fn by_id(
+    /// Fetch the actions with the given ids
+    pub async fn by_ids(
         pool: &PgPool,
         guild_id: GuildId,
-        action_id: &str,
-    ) -> Result<Self, Error> {
-        let r = sqlx::query!(
+        action_ids: &[String],
+    ) -> Result<Vec<Self>, Error> {
+        let rec = sqlx::query!(
             "
-                SELECT user_id, limit_type, created_at, action_target, limits_hit
+                SELECT action_id, user_id, limit_type, created_at, action_target, limits_hit
                 FROM user_actions
                 WHERE guild_id = $1
-                AND action_id = $2
+                AND action_id = ANY($2)
             ",
             guild_id.to_string(),
-            action_id.to_string()
+            action_ids
         )
-        .fetch_one(pool)
+        .fetch_all(pool)
         .await?;
 
+        let mut actions = Vec::new();
 
-    let actions = Self {
-            guild_id,
-            action_id: action_id.to_string(),
-            user_id: r.user_id.parse()?,
-            limit_type: r.limit_type.parse()?,
-            created_at: r.created_at,
-            action_target: r.action_target.parse()?,
-            limits_hit: r.limits_hit,
-        };
+        for r in rec {
+            actions.push(Self {
+                guild_id,
+                action_id: r.action_id,
+                user_id: r.user_id.parse()?,
+                limit_type: r.limit_type.parse()?,
+                created_at: r.created_at,
+                action_target: r.action_target.parse()?,
+                limits_hit: r.limits_hit,
+            });
+        }
 
         Ok(actions)
     }
 
-    /// Fetch actions for user
-    pub async fn user(
-        pool: &PgPool,
-        guild_id: GuildId,
-        user_id: UserId,
-    ) -> Result<Vec<Self>, Error> {
+    /// Fetch actions for guild
+    pub async fn guild(pool: &PgPool, guild_id: GuildId) -> Result<Vec<Self>, Error> {
         let rec = sqlx::query!(
             "
-                SELECT action_id, limit_type, created_at, action_target, limits_hit
+                SELECT action_id, limit_type, created_at, user_id, action_target, limits_hit
                 FROM user_actions
                 WHERE guild_id = $1
-                AND user_id = $2
             ",
-            guild_id.to_string(),
-            user_id.to_string()
+            guild_id.to_string()
         )
         .fetch_all(pool)
         .await?;
@@ -184,10 +472,10 @@ impl Action {
         for r in rec {
             actions.push(Self {
                 guild_id,
-                user_id,
                 action_id: r.action_id,
                 limit_type: r.limit_type.parse()?,
                 created_at: r.created_at,
+                user_id: r.user_id.parse()?,
                 action_target: r.action_target.parse()?,
                 limits_hit: r.limits_hit,
             });
@@ -196,15 +484,37 @@ impl Action {
         Ok(actions)
     }
 
-    /// Fetch actions for guild
-    pub async fn guild(pool: &PgPool, guild_id: GuildId) -> Result<Vec<Self>, Error> {
+    /// Fetch one page of a guilds actions matching a filter, newest first
+    pub async fn page(
+        pool: &PgPool,
+        guild_id: GuildId,
+        filter: &ActionFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Self>, Error> {
         let rec = sqlx::query!(
             "
                 SELECT action_id, limit_type, created_at, user_id, action_target, limits_hit
                 FROM user_actions
                 WHERE guild_id = $1
+                AND ($2::TEXT IS NULL OR user_id = $2)
+                AND ($3::TEXT IS NULL OR limit_type = $3)
+                AND ($4::TIMESTAMPTZ IS NULL OR created_at >= $4)
+                AND ($5::TIMESTAMPTZ IS NULL OR created_at < $5)
+                AND ($6::TEXT IS NULL OR split_part(action_target, '/', 1) = $6)
+                AND (NOT $7 OR cardinality(limits_hit) > 0)
+                ORDER BY created_at DESC
+                LIMIT $8 OFFSET $9
             ",
-            guild_id.to_string()
+            guild_id.to_string(),
+            filter.user_id.map(|u| u.to_string()),
+            filter.limit_type.as_ref().map(|t| t.to_string()),
+            filter.after,
+            filter.before,
+            filter.target,
+            filter.hits_only,
+            limit,
+            offset
         )
         .fetch_all(pool)
         .await?;
@@ -225,6 +535,38 @@ impl Action {
 
         Ok(actions)
     }
+
+    /// Count a guilds actions matching a filter
+    pub async fn count(
+        pool: &PgPool,
+        guild_id: GuildId,
+        filter: &ActionFilter,
+    ) -> Result<i64, Error> {
+        let rec = sqlx::query!(
+            "
+                SELECT COUNT(*)
+                FROM user_actions
+                WHERE guild_id = $1
+                AND ($2::TEXT IS NULL OR user_id = $2)
+                AND ($3::TEXT IS NULL OR limit_type = $3)
+                AND ($4::TIMESTAMPTZ IS NULL OR created_at >= $4)
+                AND ($5::TIMESTAMPTZ IS NULL OR created_at < $5)
+                AND ($6::TEXT IS NULL OR split_part(action_target, '/', 1) = $6)
+                AND (NOT $7 OR cardinality(limits_hit) > 0)
+            ",
+            guild_id.to_string(),
+            filter.user_id.map(|u| u.to_string()),
+            filter.limit_type.as_ref().map(|t| t.to_string()),
+            filter.after,
+            filter.before,
+            filter.target,
+            filter.hits_only
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(rec.count.unwrap_or_default())
+    }
 }
 
 #[derive(Debug)]
@@ -237,13 +579,14 @@ pub struct Limit {
     pub limit_action: UserLimitActions,
     pub limit_per: i32,
     pub limit_time: PgInterval,
+    pub limit_targets: Vec<String>,
 }
 
 impl Limit {
     pub async fn from_guild(pool: &PgPool, guild_id: GuildId) -> Result<Vec<Self>, Error> {
         let rec = sqlx::query!(
             "
-                SELECT limit_id, limit_name, limit_type, limit_action, limit_per, limit_time
+                SELECT limit_id, limit_name, limit_type, limit_action, limit_per, limit_time, limit_targets
                 FROM limits
                 WHERE guild_id = $1
             ",
@@ -263,6 +606,7 @@ impl Limit {
                 limit_action: r.limit_action.parse()?,
                 limit_per: r.limit_per,
                 limit_time: r.limit_time,
+                limit_targets: r.limit_targets,
             });
         }
 
@@ -270,6 +614,258 @@ impl Limit {
     }
 }
 
+#[derive(Debug)]
+pub struct JoinRule {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub rule_action: JoinRuleActions,
+    pub min_account_age: Option<PgInterval>,
+    pub default_avatar: bool,
+    pub username_pattern: Option<String>,
+}
+
+impl JoinRule {
+    pub async fn from_guild(pool: &PgPool, guild_id: GuildId) -> Result<Vec<Self>, Error> {
+        let rec = sqlx::query!(
+            "
+                SELECT rule_id, rule_name, rule_action, min_account_age, default_avatar, username_pattern
+                FROM join_rules
+                WHERE guild_id = $1
+            ",
+            guild_id.to_string()
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut rules = Vec::new();
+
+        for r in rec {
+            rules.push(Self {
+                rule_id: r.rule_id,
+                rule_name: r.rule_name,
+                rule_action: r.rule_action.parse()?,
+                min_account_age: r.min_account_age,
+                default_avatar: r.default_avatar,
+                username_pattern: r.username_pattern,
+            });
+        }
+
+        Ok(rules)
+    }
+}
+
+/// An action taken on a member that matched a join rule
+#[derive(Clone, Debug, Serialize)]
+pub struct JoinAction {
+    pub action_id: String,
+    pub guild_id: GuildId,
+    pub user_id: UserId,
+    /// None once the rule has been removed
+    pub rule_id: Option<String>,
+    pub rule_action: JoinRuleActions,
+    pub notes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl JoinAction {
+    /// Fetch one page of a guilds join actions, newest first
+    pub async fn page(
+        pool: &PgPool,
+        guild_id: GuildId,
+        user_id: Option<UserId>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Self>, Error> {
+        let rec = sqlx::query!(
+            "
+                SELECT action_id, user_id, rule_id, rule_action, notes, created_at
+                FROM join_actions
+                WHERE guild_id = $1
+                AND ($2::TEXT IS NULL OR user_id = $2)
+                ORDER BY created_at DESC
+                LIMIT $3 OFFSET $4
+            ",
+            guild_id.to_string(),
+            user_id.map(|u| u.to_string()),
+            limit,
+            offset
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut actions = Vec::new();
+
+        for r in rec {
+            actions.push(Self {
+                guild_id,
+                action_id: r.action_id,
+                user_id: r.user_id.parse()?,
+                rule_id: r.rule_id,
+                rule_action: r.rule_action.parse()?,
+                notes: r.notes,
+                created_at: r.created_at,
+            });
+        }
+
+        Ok(actions)
+    }
+
+    /// Count a guilds join actions
+    pub async fn count(
+        pool: &PgPool,
+        guild_id: GuildId,
+        user_id: Option<UserId>,
+    ) -> Result<i64, Error> {
+        let rec = sqlx::query!(
+            "
+                SELECT COUNT(*)
+                FROM join_actions
+                WHERE guild_id = $1
+                AND ($2::TEXT IS NULL OR user_id = $2)
+            ",
+            guild_id.to_string(),
+            user_id.map(|u| u.to_string())
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(rec.count.unwrap_or_default())
+    }
+}
+
+#[derive(Debug)]
+pub struct RaidConfig {
+    pub raid_joins: i32,
+    pub raid_time: PgInterval,
+    pub raid_action: RaidActions,
+    pub raise_verification: bool,
+    pub lockdown: bool,
+}
+
+impl RaidConfig {
+    /// Fetch the raid detection settings of a guild, if raid detection is enabled
+    pub async fn from_guild(pool: &PgPool, guild_id: GuildId) -> Result<Option<Self>, Error> {
+        let rec = sqlx::query!(
+            "
+                SELECT raid_joins, raid_time, raid_action, raise_verification, lockdown
+                FROM raid_config
+                WHERE guild_id = $1
+            ",
+            guild_id.to_string()
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        let Some(r) = rec else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            raid_joins: r.raid_joins,
+            raid_time: r.raid_time,
+            raid_action: r.raid_action.parse()?,
+            raise_verification: r.raise_verification,
+            lockdown: r.lockdown,
+        }))
+    }
+
+    /// Returns true if replacing these settings with new ones makes raids harder to detect
+    /// or softens the response to them
+    pub fn weakened_by(&self, new: &Self) -> bool {
+        new.raid_joins > self.raid_joins
+            || crate::utils::pg_interval_to_secs(&new.raid_time)
+                < crate::utils::pg_interval_to_secs(&self.raid_time)
+            || new.raid_action < self.raid_action
+            || (self.raise_verification && !new.raise_verification)
+            || (self.lockdown && !new.lockdown)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SpamConfig {
+    pub spam_time: PgInterval,
+    pub spam_duplicates: i32,
+    pub spam_channels: i32,
+    pub spam_mentions: i32,
+    pub spam_invites: bool,
+}
+
+impl SpamConfig {
+    /// Fetch the spam detection settings of a guild, if spam detection is enabled
+    pub async fn from_guild(pool: &PgPool, guild_id: GuildId) -> Result<Option<Self>, Error> {
+        let rec = sqlx::query!(
+            "
+                SELECT spam_time, spam_duplicates, spam_channels, spam_mentions, spam_invites
+                FROM spam_config
+                WHERE guild_id = $1
+            ",
+            guild_id.to_string()
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        let Some(r) = rec else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            spam_time: r.spam_time,
+            spam_duplicates: r.spam_duplicates,
+            spam_channels: r.spam_channels,
+            spam_mentions: r.spam_mentions,
+            spam_invites: r.spam_invites,
+        }))
+    }
+
+    /// Returns true if replacing these settings with new ones lets more spam through
+    pub fn weakened_by(&self, new: &Self) -> bool {
+        // A threshold of 0 disables its check, so it is the weakest threshold
+        let raised = |old: i32, new: i32| old != 0 && (new == 0 || new > old);
+
+        crate::utils::pg_interval_to_secs(&new.spam_time)
+            < crate::utils::pg_interval_to_secs(&self.spam_time)
+            || raised(self.spam_duplicates, new.spam_duplicates)
+            || raised(self.spam_channels, new.spam_channels)
+            || raised(self.spam_mentions, new.spam_mentions)
+            || (self.spam_invites && !new.spam_invites)
+    }
+}
+
+#[derive(Debug)]
+pub struct LimitWeight {
+    pub limit_type: UserLimitTypes,
+    pub weight: i32,
+}
+
+impl LimitWeight {
+    /// Fetch the threat score weights configured for a guild
+    ///
+    /// Action types without a configured weight have a weight of 1
+    pub async fn from_guild(pool: &PgPool, guild_id: GuildId) -> Result<Vec<Self>, Error> {
+        let rec = sqlx::query!(
+            "
+                SELECT limit_type, weight
+                FROM limit_weights
+                WHERE guild_id = $1
+            ",
+            guild_id.to_string()
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut weights = Vec::new();
+
+        for r in rec {
+            weights.push(Self {
+                limit_type: r.limit_type.parse()?,
+                weight: r.weight,
+            });
+        }
+
+        Ok(weights)
+    }
+}
+
 #[derive(Debug)]
 pub struct CurrentUserLimitsHit {
     pub limit: Limit,
@@ -277,52 +873,138 @@ pub struct CurrentUserLimitsHit {
 }
 
 impl CurrentUserLimitsHit {
-    /// Returns a list of all limits that have been hit for a specific guild
-    pub async fn hit(guild_id: GuildId, pool: &PgPool) -> Result<Vec<Self>, Error> {
+    /// Returns a list of all limits that have been hit by a user in a specific guild
+    ///
+    /// Actions are read through `conn` so that actions recorded in an open transaction are counted
+    pub async fn hit(
+        guild_id: GuildId,
+        user_id: UserId,
+        pool: &PgPool,
+        conn: &mut PgConnection,
+    ) -> Result<Vec<Self>, Error> {
         let limits = Limit::from_guild(pool, guild_id).await?;
 
         let mut hits = Vec::new();
 
         for limit in limits {
+            if limit.limit_type == UserLimitTypes::ThreatScore {
+                if let Some(hit) =
+                    Self::threat_score_hit(guild_id, user_id, &mut *conn, limit).await?
+                {
+                    hits.push(hit);
+                }
+                continue;
+            }
+
             let mut cause = Vec::new();
 
             // Find all actions that apply to this limit
             let rec = sqlx::query!(
                 "
-                    SELECT action_id, created_at, user_id, action_target, limits_hit
+                    SELECT action_id, created_at, action_target, limits_hit
                     FROM user_actions
                     WHERE guild_id = $1
+                    AND user_id = $6
                     AND NOT($4 = ANY(limits_hit)) -- Not already handled
                     AND NOW() - created_at < $2
                     AND limit_type = $3
+                    AND (cardinality($5::text[]) = 0 OR split_part(action_target, '/', 1) = ANY($5)) -- Protected targets only, if any (messages are scoped by channel)
                 ",
                 guild_id.to_string(),
                 limit.limit_time,
                 limit.limit_type.to_string(),
-                limit.limit_id
+                limit.limit_id,
+                &limit.limit_targets,
+                user_id.to_string()
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await?;
 
             for r in rec {
                 cause.push(Action {
                     guild_id,
+                    user_id,
                     limit_type: limit.limit_type.clone(),
                     created_at: r.created_at,
-                    user_id: r.user_id.parse()?,
                     action_target: r.action_target.parse()?,
                     action_id: r.action_id,
                     limits_hit: r.limits_hit,
                 });
             }
 
-            if cause.len() >= limit.limit_per as usize {
+            // Tampering with Skynet is hit immediately, as it stops Skynet from enforcing other limits
+            let limit_per = if limit.limit_type == UserLimitTypes::BotTamper {
+                1
+            } else {
+                limit.limit_per as usize
+            };
+
+            if cause.len() >= limit_per {
                 hits.push(Self { limit, cause });
             }
         }
 
         Ok(hits)
     }
+
+    /// Returns a hit if the weighted score of a user across all action types
+    /// reaches the threshold of a threat score limit
+    async fn threat_score_hit(
+        guild_id: GuildId,
+        user_id: UserId,
+        conn: &mut PgConnection,
+        limit: Limit,
+    ) -> Result<Option<Self>, Error> {
+        let rec = sqlx::query!(
+            "
+                SELECT user_actions.action_id, user_actions.limit_type, user_actions.created_at,
+                user_actions.action_target, user_actions.limits_hit,
+                COALESCE(limit_weights.weight, 1) AS \"weight!\"
+                FROM user_actions
+                LEFT JOIN limit_weights
+                ON limit_weights.guild_id = user_actions.guild_id
+                AND limit_weights.limit_type = user_actions.limit_type
+                WHERE user_actions.guild_id = $1
+                AND user_actions.user_id = $5
+                AND NOT($3 = ANY(user_actions.limits_hit)) -- Not already handled
+                AND NOW() - user_actions.created_at < $2
+                AND (cardinality($4::text[]) = 0 OR split_part(user_actions.action_target, '/', 1) = ANY($4)) -- Protected targets only, if any (messages are scoped by channel)
+            ",
+            guild_id.to_string(),
+            limit.limit_time,
+            limit.limit_id,
+            &limit.limit_targets,
+            user_id.to_string()
+        )
+        .fetch_all(conn)
+        .await?;
+
+        let mut score: i64 = 0;
+        let mut cause = Vec::new();
+
+        for r in rec {
+            score += r.weight as i64;
+
+            // Actions with a weight of zero do not contribute to the score
+            if r.weight > 0 {
+                cause.push(Action {
+                    guild_id,
+                    user_id,
+                    limit_type: r.limit_type.parse()?,
+                    created_at: r.created_at,
+                    action_target: r.action_target.parse()?,
+                    action_id: r.action_id,
+                    limits_hit: r.limits_hit,
+                });
+            }
+        }
+
+        if score >= limit.limit_per as i64 {
+            Ok(Some(Self { limit, cause }))
+        } else {
+            Ok(None)
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -331,44 +1013,97 @@ pub struct PastHitLimits {
     pub user_id: UserId,
     pub guild_id: GuildId,
     pub limit_id: String,
+    pub limit_name: String,
     pub cause: Vec<Action>,
     pub notes: Vec<String>,
+    /// Whether the punishment of the limit could not be applied
+    pub punishment_failed: bool,
     pub created_at: DateTime<Utc>,
 }
 
 impl PastHitLimits {
-    /// Fetch actions for guild
-    pub async fn guild(pool: &PgPool, guild_id: GuildId) -> Result<Vec<Self>, Error> {
+    /// Fetch one page of a guilds hit limits, newest first
+    pub async fn page(
+        pool: &PgPool,
+        guild_id: GuildId,
+        user_id: Option<UserId>,
+        limit_id: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Self>, Error> {
         let rec = sqlx::query!(
             "
-                SELECT id, user_id, limit_id, cause, notes, created_at FROM past_hit_limits
-                WHERE guild_id = $1
+                SELECT p.id, p.user_id, p.limit_id, l.limit_name, p.cause, p.notes, p.punishment_failed, p.created_at
+                FROM past_hit_limits p
+                JOIN limits l ON l.limit_id = p.limit_id
+                WHERE p.guild_id = $1
+                AND ($2::TEXT IS NULL OR p.user_id = $2)
+                AND ($3::TEXT IS NULL OR p.limit_id = $3)
+                ORDER BY p.created_at DESC
+                LIMIT $4 OFFSET $5
             ",
-            guild_id.to_string()
+            guild_id.to_string(),
+            user_id.map(|u| u.to_string()),
+            limit_id,
+            limit,
+            offset
         )
         .fetch_all(pool)
         .await?;
 
-        let mut hits = Vec::new();
+        // Fetch the causes of every hit on the page at once
+        let cause_ids = rec
+            .iter()
+            .flat_map(|r| r.cause.iter().cloned())
+            .collect::<Vec<_>>();
 
-        for r in rec {
-            let mut cause = vec![];
+        let causes = Action::by_ids(pool, guild_id, &cause_ids).await?;
 
-            for action in r.cause {
-                cause.push(Action::by_id(pool, guild_id, &action).await?);
-            }
+        let mut hits = Vec::new();
 
+        for r in rec {
             hits.push(Self {
                 guild_id,
+                cause: causes
+                    .iter()
+                    .filter(|a| r.cause.contains(&a.action_id))
+                    .cloned()
+                    .collect(),
                 id: r.id,
                 limit_id: r.limit_id,
+                limit_name: r.limit_name,
                 created_at: r.created_at,
                 user_id: r.user_id.parse()?,
                 notes: r.notes,
-                cause,
+                punishment_failed: r.punishment_failed,
             });
         }
 
         Ok(hits)
-    }    
-}
\ No newline at end of file
+    }
+
+    /// Count a guilds hit limits
+    pub async fn count(
+        pool: &PgPool,
+        guild_id: GuildId,
+        user_id: Option<UserId>,
+        limit_id: Option<&str>,
+    ) -> Result<i64, Error> {
+        let rec = sqlx::query!(
+            "
+                SELECT COUNT(*)
+                FROM past_hit_limits
+                WHERE guild_id = $1
+                AND ($2::TEXT IS NULL OR user_id = $2)
+                AND ($3::TEXT IS NULL OR limit_id = $3)
+            ",
+            guild_id.to_string(),
+            user_id.map(|u| u.to_string()),
+            limit_id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(rec.count.unwrap_or_default())
+    }
+}
@@ -0,0 +1,1765 @@
+use poise::serenity_prelude::{
+    Attachment, ButtonStyle, ChannelId, CreateActionRow, CreateAttachment, CreateButton,
+    CreateEmbed, Guild, GuildId, RoleId,
+};
+use poise::CreateReply;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::types::PgInterval;
+use sqlx::{PgConnection, PgPool};
+
+use crate::core::{
+    Capability, JoinRuleActions, RaidActions, RaidConfig, SafeguardMode, Severity, SpamConfig,
+    UserLimitActions, UserLimitTypes,
+};
+
+type Error = crate::Error;
+type Context<'a> = crate::Context<'a>;
+
+/// Bumped whenever the exported document changes in a way older versions cannot read
+const CONFIG_VERSION: u32 = 2;
+
+/// First version containing raid and spam detection, join rules and capabilities
+const DETECTION_VERSION: u32 = 2;
+
+/// Largest config file that will be imported
+const MAX_IMPORT_SIZE: u32 = 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct ConfigSettings {
+    pub log_channel: Option<String>,
+    pub quarantine_role: Option<String>,
+    pub dm_severity: Option<String>,
+    pub safeguard_mode: Option<String>,
+    /// Seconds
+    pub safeguard_cooldown: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ConfigLimit {
+    pub limit_name: String,
+    pub limit_type: String,
+    pub limit_action: String,
+    pub limit_per: i32,
+    /// Seconds
+    pub limit_time: i64,
+    #[serde(default)]
+    pub limit_targets: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ConfigWeight {
+    pub limit_type: String,
+    pub weight: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ConfigWhitelistEntry {
+    pub target_id: String,
+    /// user or role
+    pub target_type: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ConfigRaid {
+    pub raid_joins: i32,
+    /// Seconds
+    pub raid_time: i64,
+    pub raid_action: String,
+    #[serde(default)]
+    pub raise_verification: bool,
+    #[serde(default)]
+    pub lockdown: bool,
+}
+
+impl ConfigRaid {
+    fn to_core(&self) -> Option<RaidConfig> {
+        Some(RaidConfig {
+            raid_joins: self.raid_joins,
+            raid_time: secs_to_pg_interval(self.raid_time),
+            raid_action: self.raid_action.parse().ok()?,
+            raise_verification: self.raise_verification,
+            lockdown: self.lockdown,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ConfigSpam {
+    /// Seconds
+    pub spam_time: i64,
+    #[serde(default)]
+    pub spam_duplicates: i32,
+    #[serde(default)]
+    pub spam_channels: i32,
+    #[serde(default)]
+    pub spam_mentions: i32,
+    #[serde(default)]
+    pub spam_invites: bool,
+}
+
+impl ConfigSpam {
+    fn to_core(&self) -> SpamConfig {
+        SpamConfig {
+            spam_time: secs_to_pg_interval(self.spam_time),
+            spam_duplicates: self.spam_duplicates,
+            spam_channels: self.spam_channels,
+            spam_mentions: self.spam_mentions,
+            spam_invites: self.spam_invites,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ConfigJoinRule {
+    pub rule_name: String,
+    pub rule_action: String,
+    /// Seconds
+    #[serde(default)]
+    pub min_account_age: Option<i64>,
+    #[serde(default)]
+    pub default_avatar: bool,
+    #[serde(default)]
+    pub username_pattern: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ConfigCapability {
+    pub target_id: String,
+    /// user or role
+    pub target_type: String,
+    pub capability: String,
+}
+
+fn secs_to_pg_interval(secs: i64) -> PgInterval {
+    PgInterval {
+        months: 0,
+        days: 0,
+        microseconds: secs * 1000000,
+    }
+}
+
+/// A guilds Skynet configuration, as exported by ``/config export``
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GuildConfig {
+    pub version: u32,
+    #[serde(default)]
+    pub settings: ConfigSettings,
+    #[serde(default)]
+    pub limits: Vec<ConfigLimit>,
+    #[serde(default)]
+    pub weights: Vec<ConfigWeight>,
+    #[serde(default)]
+    pub admins: Vec<String>,
+    #[serde(default)]
+    pub admin_roles: Vec<String>,
+    #[serde(default)]
+    pub whitelist: Vec<ConfigWhitelistEntry>,
+    /// Raid detection is disabled if None
+    #[serde(default)]
+    pub raid: Option<ConfigRaid>,
+    /// Spam detection is disabled if None
+    #[serde(default)]
+    pub spam: Option<ConfigSpam>,
+    #[serde(default)]
+    pub join_rules: Vec<ConfigJoinRule>,
+    #[serde(default)]
+    pub capabilities: Vec<ConfigCapability>,
+}
+
+/// What importing a config would change
+#[derive(Default)]
+pub struct ConfigDiff {
+    pub lines: Vec<String>,
+    /// Whether the import lowers protection in any way
+    pub weakens: bool,
+    /// Whether the import changes who is an admin
+    pub changes_admins: bool,
+    /// Limits that exist now but not in the imported config: (id, name)
+    pub removed_limits: Vec<(String, String)>,
+    /// Join rules that exist now but not in the imported config: (id, name)
+    pub removed_join_rules: Vec<(String, String)>,
+    pub disables_raid: bool,
+    pub disables_spam: bool,
+}
+
+/// IDs of the rows a config was exported from, in the same order as the config
+#[derive(Default)]
+pub struct ConfigIds {
+    pub limits: Vec<String>,
+    pub join_rules: Vec<String>,
+}
+
+impl GuildConfig {
+    /// Exports the current configuration of a guild
+    pub async fn export(pool: &PgPool, guild_id: GuildId) -> Result<Self, Error> {
+        Ok(Self::export_with_ids(pool, guild_id).await?.0)
+    }
+
+    /// Like export, but also returns the IDs of the exported limits and join rules
+    async fn export_with_ids(pool: &PgPool, guild_id: GuildId) -> Result<(Self, ConfigIds), Error> {
+        let settings = sqlx::query!(
+            "
+                SELECT log_channel, quarantine_role, dm_severity, safeguard_mode, safeguard_cooldown
+                FROM guilds
+                WHERE guild_id = $1
+            ",
+            guild_id.to_string()
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or("Please run ``/setup`` to get started!")?;
+
+        let limits = crate::core::Limit::from_guild(pool, guild_id).await?;
+        let weights = crate::core::LimitWeight::from_guild(pool, guild_id).await?;
+
+        let admins = sqlx::query!(
+            "SELECT user_id FROM guild_admins WHERE guild_id = $1",
+            guild_id.to_string()
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let admin_roles = sqlx::query!(
+            "SELECT role_id FROM guild_admin_roles WHERE guild_id = $1",
+            guild_id.to_string()
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let whitelist = sqlx::query!(
+            "SELECT target_id, target_type FROM guild_whitelist WHERE guild_id = $1",
+            guild_id.to_string()
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let raid = RaidConfig::from_guild(pool, guild_id).await?;
+        let spam = SpamConfig::from_guild(pool, guild_id).await?;
+        let join_rules = crate::core::JoinRule::from_guild(pool, guild_id).await?;
+
+        let capabilities = sqlx::query!(
+            "SELECT target_id, target_type, capability FROM guild_capabilities WHERE guild_id = $1",
+            guild_id.to_string()
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let ids = ConfigIds {
+            limits: limits.iter().map(|l| l.limit_id.clone()).collect(),
+            join_rules: join_rules.iter().map(|r| r.rule_id.clone()).collect(),
+        };
+
+        let config = Self {
+            version: CONFIG_VERSION,
+            settings: ConfigSettings {
+                log_channel: settings.log_channel,
+                quarantine_role: settings.quarantine_role,
+                dm_severity: settings.dm_severity,
+                safeguard_mode: settings.safeguard_mode,
+                safeguard_cooldown: crate::utils::pg_interval_to_secs(&settings.safeguard_cooldown),
+            },
+            limits: limits
+                .into_iter()
+                .map(|l| ConfigLimit {
+                    limit_name: l.limit_name,
+                    limit_type: l.limit_type.to_string(),
+                    limit_action: l.limit_action.to_string(),
+                    limit_per: l.limit_per,
+                    limit_time: crate::utils::pg_interval_to_secs(&l.limit_time),
+                    limit_targets: l.limit_targets,
+                })
+                .collect(),
+            weights: weights
+                .into_iter()
+                .map(|w| ConfigWeight {
+                    limit_type: w.limit_type.to_string(),
+                    weight: w.weight,
+                })
+                .collect(),
+            admins: admins.into_iter().map(|a| a.user_id).collect(),
+            admin_roles: admin_roles.into_iter().map(|r| r.role_id).collect(),
+            whitelist: whitelist
+                .into_iter()
+                .map(|w| ConfigWhitelistEntry {
+                    target_id: w.target_id,
+                    target_type: w.target_type,
+                })
+                .collect(),
+            raid: raid.map(|r| ConfigRaid {
+                raid_joins: r.raid_joins,
+                raid_time: crate::utils::pg_interval_to_secs(&r.raid_time),
+                raid_action: r.raid_action.to_string(),
+                raise_verification: r.raise_verification,
+                lockdown: r.lockdown,
+            }),
+            spam: spam.map(|s| ConfigSpam {
+                spam_time: crate::utils::pg_interval_to_secs(&s.spam_time),
+                spam_duplicates: s.spam_duplicates,
+                spam_channels: s.spam_channels,
+                spam_mentions: s.spam_mentions,
+                spam_invites: s.spam_invites,
+            }),
+            join_rules: join_rules
+                .into_iter()
+                .map(|r| ConfigJoinRule {
+                    rule_name: r.rule_name,
+                    rule_action: r.rule_action.to_string(),
+                    min_account_age: r
+                        .min_account_age
+                        .map(|a| crate::utils::pg_interval_to_secs(&a)),
+                    default_avatar: r.default_avatar,
+                    username_pattern: r.username_pattern,
+                })
+                .collect(),
+            capabilities: capabilities
+                .into_iter()
+                .map(|c| ConfigCapability {
+                    target_id: c.target_id,
+                    target_type: c.target_type,
+                    capability: c.capability,
+                })
+                .collect(),
+        };
+
+        Ok((config, ids))
+    }
+
+    /// Checks that the config is valid for a guild
+    ///
+    /// Channels and roles that do not exist in the guild, for example when importing a config
+    /// from another server, are dropped. A warning is returned for each of them. A missing log
+    /// channel is replaced by the current one, so an import does not silently disable logging
+    pub fn validate(&mut self, guild: &Guild, current: &GuildConfig) -> Result<Vec<String>, Error> {
+        self.validate_with(
+            current,
+            |id| {
+                id.parse::<ChannelId>()
+                    .is_ok_and(|id| guild.channels.get(&id).is_some())
+            },
+            |id| {
+                id.parse::<RoleId>()
+                    .is_ok_and(|id| id.get() != guild.id.get() && guild.roles.get(&id).is_some())
+            },
+        )
+    }
+
+    /// Like validate, with the lookups of channels and roles passed in
+    fn validate_with(
+        &mut self,
+        current: &GuildConfig,
+        has_channel: impl Fn(&str) -> bool,
+        has_role: impl Fn(&str) -> bool,
+    ) -> Result<Vec<String>, Error> {
+        if self.version == 0 || self.version > CONFIG_VERSION {
+            return Err(format!(
+                "Unsupported config version {}, expected at most {}",
+                self.version, CONFIG_VERSION
+            )
+            .into());
+        }
+
+        let mut warnings = Vec::new();
+
+        // Settings
+        if let Some(ref log_channel) = self.settings.log_channel {
+            if !has_channel(log_channel) {
+                warnings.push(format!(
+                    "Log channel {} does not exist here, the current log channel is kept",
+                    log_channel
+                ));
+                self.settings.log_channel = current.settings.log_channel.clone();
+            }
+        }
+
+        if let Some(ref quarantine_role) = self.settings.quarantine_role {
+            if !has_role(quarantine_role) {
+                warnings.push(format!(
+                    "Quarantine role {} does not exist here",
+                    quarantine_role
+                ));
+                self.settings.quarantine_role = None;
+            }
+        }
+
+        if let Some(ref dm_severity) = self.settings.dm_severity {
+            dm_severity
+                .parse::<Severity>()
+                .map_err(|_| format!("Invalid DM severity: {}", dm_severity))?;
+        }
+
+        if let Some(ref safeguard_mode) = self.settings.safeguard_mode {
+            safeguard_mode
+                .parse::<SafeguardMode>()
+                .map_err(|_| format!("Invalid safeguard mode: {}", safeguard_mode))?;
+        }
+
+        if self.settings.safeguard_cooldown < 0 {
+            return Err("Safeguard cooldown cannot be negative".into());
+        }
+
+        // Limits
+        for limit in self.limits.iter_mut() {
+            limit
+                .limit_type
+                .parse::<UserLimitTypes>()
+                .map_err(|_| format!("Invalid limit type: {}", limit.limit_type))?;
+
+            limit
+                .limit_action
+                .parse::<UserLimitActions>()
+                .map_err(|_| format!("Invalid limit action: {}", limit.limit_action))?;
+
+            if limit.limit_per < 1 {
+                return Err(
+                    format!("Limit {} must allow at least 1 action", limit.limit_name).into(),
+                );
+            }
+
+            if limit.limit_time < 1 {
+                return Err(format!("Limit {} must have an interval", limit.limit_name).into());
+            }
+
+            let before = limit.limit_targets.len();
+
+            limit
+                .limit_targets
+                .retain(|t| has_channel(t) || has_role(t));
+
+            if limit.limit_targets.len() < before {
+                warnings.push(format!(
+                    "{} protected targets of limit {} do not exist here",
+                    before - limit.limit_targets.len(),
+                    limit.limit_name
+                ));
+
+                // A limit whose targets all vanished would otherwise silently cover everything
+                if limit.limit_targets.is_empty() {
+                    return Err(format!(
+                        "None of the protected targets of limit {} exist here",
+                        limit.limit_name
+                    )
+                    .into());
+                }
+            }
+        }
+
+        // Weights
+        for weight in self.weights.iter() {
+            let limit_type = weight
+                .limit_type
+                .parse::<UserLimitTypes>()
+                .map_err(|_| format!("Invalid weight limit type: {}", weight.limit_type))?;
+
+            if limit_type == UserLimitTypes::ThreatScore {
+                return Err("Threat scores cannot be weighed".into());
+            }
+
+            if weight.weight < 0 {
+                return Err("Weights cannot be negative".into());
+            }
+        }
+
+        // Admins
+        for admin in self.admins.iter() {
+            admin
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid admin user id: {}", admin))?;
+        }
+
+        let before = self.admin_roles.len();
+        self.admin_roles.retain(|r| has_role(r));
+
+        if self.admin_roles.len() < before {
+            warnings.push(format!(
+                "{} admin roles do not exist here",
+                before - self.admin_roles.len()
+            ));
+        }
+
+        // Whitelist
+        for entry in self.whitelist.iter() {
+            match entry.target_type.as_str() {
+                "user" => {
+                    entry
+                        .target_id
+                        .parse::<u64>()
+                        .map_err(|_| format!("Invalid whitelisted user id: {}", entry.target_id))?;
+                }
+                "role" => {}
+                _ => {
+                    return Err(
+                        format!("Invalid whitelist target type: {}", entry.target_type).into(),
+                    )
+                }
+            }
+        }
+
+        let before = self.whitelist.len();
+        self.whitelist
+            .retain(|e| e.target_type == "user" || has_role(&e.target_id));
+
+        if self.whitelist.len() < before {
+            warnings.push(format!(
+                "{} whitelisted roles do not exist here",
+                before - self.whitelist.len()
+            ));
+        }
+
+        // Raid and spam detection, with the same bounds as /raid setup and /spam setup
+        if let Some(ref raid) = self.raid {
+            raid.raid_action
+                .parse::<RaidActions>()
+                .map_err(|_| format!("Invalid raid action: {}", raid.raid_action))?;
+
+            if raid.raid_joins < 2 {
+                return Err("A raid must consist of at least 2 joins".into());
+            }
+
+            if raid.raid_time <= 0 {
+                return Err("The raid time interval must be positive".into());
+            }
+        }
+
+        if let Some(ref spam) = self.spam {
+            if spam.spam_duplicates < 0 || spam.spam_channels < 0 || spam.spam_mentions < 0 {
+                return Err("Spam thresholds cannot be negative".into());
+            }
+
+            if spam.spam_time <= 0 || spam.spam_time > crate::spam::MAX_TRACKED.as_secs() as i64 {
+                return Err(format!(
+                    "The spam time interval must be positive and at most {:?}",
+                    crate::spam::MAX_TRACKED
+                )
+                .into());
+            }
+        }
+
+        // Join rules
+        for rule in self.join_rules.iter() {
+            rule.rule_action
+                .parse::<JoinRuleActions>()
+                .map_err(|_| format!("Invalid join rule action: {}", rule.rule_action))?;
+
+            if rule.min_account_age.is_some_and(|age| age < 0) {
+                return Err(
+                    format!("Join rule {} has a negative account age", rule.rule_name).into(),
+                );
+            }
+
+            if let Some(ref pattern) = rule.username_pattern {
+                regex::Regex::new(pattern).map_err(|e| {
+                    format!(
+                        "Join rule {} has an invalid username pattern: {}",
+                        rule.rule_name, e
+                    )
+                })?;
+            }
+
+            if rule.min_account_age.is_none()
+                && !rule.default_avatar
+                && rule.username_pattern.is_none()
+            {
+                return Err(format!("Join rule {} has no conditions", rule.rule_name).into());
+            }
+        }
+
+        // Capabilities
+        for grant in self.capabilities.iter() {
+            grant
+                .capability
+                .parse::<Capability>()
+                .map_err(|_| format!("Invalid capability: {}", grant.capability))?;
+
+            match grant.target_type.as_str() {
+                "user" => {
+                    grant.target_id.parse::<u64>().map_err(|_| {
+                        format!("Invalid capability holder user id: {}", grant.target_id)
+                    })?;
+                }
+                "role" => {}
+                _ => {
+                    return Err(
+                        format!("Invalid capability target type: {}", grant.target_type).into(),
+                    )
+                }
+            }
+        }
+
+        let before = self.capabilities.len();
+        self.capabilities
+            .retain(|c| c.target_type == "user" || has_role(&c.target_id));
+
+        if self.capabilities.len() < before {
+            warnings.push(format!(
+                "{} capabilities were granted to roles that do not exist here",
+                before - self.capabilities.len()
+            ));
+        }
+
+        Ok(warnings)
+    }
+
+    /// Configs exported before raid and spam detection, join rules and capabilities were
+    /// exported leave them unchanged rather than clearing them
+    pub fn keep_missing_sections(&mut self, current: &GuildConfig) {
+        if self.version < DETECTION_VERSION {
+            self.raid = current.raid.clone();
+            self.spam = current.spam.clone();
+            self.join_rules = current.join_rules.clone();
+            self.capabilities = current.capabilities.clone();
+        }
+    }
+
+    /// Compares this config against the current one. Limits and join rules are matched by name
+    pub fn diff(&self, current: &GuildConfig, current_ids: &ConfigIds) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+
+        // Settings
+        let settings = [
+            (
+                "Log Channel",
+                &current.settings.log_channel,
+                &self.settings.log_channel,
+            ),
+            (
+                "Quarantine Role",
+                &current.settings.quarantine_role,
+                &self.settings.quarantine_role,
+            ),
+            (
+                "DM Alerts",
+                &current.settings.dm_severity,
+                &self.settings.dm_severity,
+            ),
+            (
+                "Safeguards",
+                &current.settings.safeguard_mode,
+                &self.settings.safeguard_mode,
+            ),
+        ];
+
+        for (name, old, new) in settings {
+            if old != new {
+                diff.lines.push(format!(
+                    "~ {}: {} -> {}",
+                    name,
+                    old.as_deref().unwrap_or("None"),
+                    new.as_deref().unwrap_or("None")
+                ));
+            }
+        }
+
+        if current.settings.safeguard_mode.is_some() && self.settings.safeguard_mode.is_none() {
+            diff.weakens = true;
+        }
+
+        // Same rules as /settings log_channel and /settings dm_alerts
+        if current.settings.log_channel.is_some() && self.settings.log_channel.is_none() {
+            diff.weakens = true;
+        }
+
+        let severity_of =
+            |s: &Option<String>| s.as_deref().and_then(|s| s.parse::<Severity>().ok());
+
+        let fewer_alerts = match (
+            severity_of(&current.settings.dm_severity),
+            severity_of(&self.settings.dm_severity),
+        ) {
+            (Some(_), None) => true,
+            (Some(old), Some(new)) => new > old,
+            (None, _) => false,
+        };
+
+        if fewer_alerts {
+            diff.weakens = true;
+        }
+
+        if current.settings.safeguard_cooldown != self.settings.safeguard_cooldown {
+            diff.lines.push(format!(
+                "~ Safeguard Cooldown: {}s -> {}s",
+                current.settings.safeguard_cooldown, self.settings.safeguard_cooldown
+            ));
+
+            if self.settings.safeguard_cooldown < current.settings.safeguard_cooldown {
+                diff.weakens = true;
+            }
+        }
+
+        // Limits, each current limit can only be matched once
+        let mut matched = vec![false; current.limits.len()];
+
+        for limit in self.limits.iter() {
+            let old = current
+                .limits
+                .iter()
+                .enumerate()
+                .find(|(i, l)| !matched[*i] && l.limit_name == limit.limit_name);
+
+            match old {
+                Some((i, old)) => {
+                    matched[i] = true;
+
+                    if old != limit {
+                        diff.lines.push(format!("~ Limit {}", limit.limit_name));
+
+                        let old_action = old.limit_action.parse::<UserLimitActions>().ok();
+                        let new_action = limit.limit_action.parse::<UserLimitActions>().ok();
+
+                        if limit.limit_per > old.limit_per
+                            || limit.limit_time < old.limit_time
+                            || old.limit_type != limit.limit_type
+                            || old.limit_targets != limit.limit_targets
+                            || new_action.map(|a| a.severity()) < old_action.map(|a| a.severity())
+                        {
+                            diff.weakens = true;
+                        }
+                    }
+                }
+                None => diff.lines.push(format!("+ Limit {}", limit.limit_name)),
+            }
+        }
+
+        for (i, old) in current.limits.iter().enumerate() {
+            if !matched[i] {
+                diff.lines.push(format!("- Limit {}", old.limit_name));
+                diff.weakens = true;
+
+                if let Some(id) = current_ids.limits.get(i) {
+                    diff.removed_limits
+                        .push((id.clone(), old.limit_name.clone()));
+                }
+            }
+        }
+
+        // Weights, unweighted action types count as 1
+        for variant in <UserLimitTypes as strum::VariantNames>::VARIANTS {
+            let weight_of = |cfg: &GuildConfig| {
+                cfg.weights
+                    .iter()
+                    .find(|w| w.limit_type == *variant)
+                    .map(|w| w.weight)
+                    .unwrap_or(1)
+            };
+
+            let (old, new) = (weight_of(current), weight_of(self));
+
+            if old != new {
+                diff.lines
+                    .push(format!("~ Weight of {}: {} -> {}", variant, old, new));
+
+                if new < old {
+                    diff.weakens = true;
+                }
+            }
+        }
+
+        // Admins and admin roles
+        let mut list_diff = |name: &str, old: Vec<String>, new: Vec<String>| {
+            let mut changed = false;
+
+            for n in new.iter().filter(|n| !old.contains(n)) {
+                diff.lines.push(format!("+ {} {}", name, n));
+                changed = true;
+            }
+
+            for o in old.iter().filter(|o| !new.contains(o)) {
+                diff.lines.push(format!("- {} {}", name, o));
+                changed = true;
+            }
+
+            changed
+        };
+
+        let admins_changed = list_diff("Admin", current.admins.clone(), self.admins.clone());
+        let admin_roles_changed = list_diff(
+            "Admin Role",
+            current.admin_roles.clone(),
+            self.admin_roles.clone(),
+        );
+
+        let whitelist_key = |e: &ConfigWhitelistEntry| format!("{} {}", e.target_type, e.target_id);
+
+        let old_whitelist = current
+            .whitelist
+            .iter()
+            .map(whitelist_key)
+            .collect::<Vec<_>>();
+        let new_whitelist = self.whitelist.iter().map(whitelist_key).collect::<Vec<_>>();
+
+        let whitelist_added = new_whitelist.iter().any(|n| !old_whitelist.contains(n));
+
+        list_diff("Whitelisted", old_whitelist, new_whitelist);
+
+        // Capabilities, Manage Admins can be used to make oneself an admin
+        let capability_key =
+            |c: &ConfigCapability| format!("{} {} {}", c.capability, c.target_type, c.target_id);
+
+        let old_capabilities = current
+            .capabilities
+            .iter()
+            .map(capability_key)
+            .collect::<Vec<_>>();
+        let new_capabilities = self
+            .capabilities
+            .iter()
+            .map(capability_key)
+            .collect::<Vec<_>>();
+
+        let capability_removed = old_capabilities
+            .iter()
+            .any(|o| !new_capabilities.contains(o));
+        let manage_admins_changed = old_capabilities
+            .iter()
+            .filter(|o| !new_capabilities.contains(o))
+            .chain(
+                new_capabilities
+                    .iter()
+                    .filter(|n| !old_capabilities.contains(n)),
+            )
+            .any(|c| c.starts_with(&format!("{} ", Capability::ManageAdmins)));
+
+        list_diff("Capability", old_capabilities, new_capabilities);
+
+        diff.changes_admins = admins_changed || admin_roles_changed || manage_admins_changed;
+
+        // Revoking capabilities can lock out the moderators defending the server
+        if whitelist_added || capability_removed {
+            diff.weakens = true;
+        }
+
+        // Raid and spam detection
+        match (&current.raid, &self.raid) {
+            (None, Some(_)) => diff.lines.push("+ Raid Detection".to_string()),
+            (Some(_), None) => {
+                diff.lines.push("- Raid Detection".to_string());
+                diff.weakens = true;
+                diff.disables_raid = true;
+            }
+            (Some(old), Some(new)) if old != new => {
+                diff.lines.push("~ Raid Detection".to_string());
+
+                match (old.to_core(), new.to_core()) {
+                    (Some(old), Some(new)) if !old.weakened_by(&new) => {}
+                    _ => diff.weakens = true,
+                }
+            }
+            _ => {}
+        }
+
+        match (&current.spam, &self.spam) {
+            (None, Some(_)) => diff.lines.push("+ Spam Detection".to_string()),
+            (Some(_), None) => {
+                diff.lines.push("- Spam Detection".to_string());
+                diff.weakens = true;
+                diff.disables_spam = true;
+            }
+            (Some(old), Some(new)) if old != new => {
+                diff.lines.push("~ Spam Detection".to_string());
+
+                if old.to_core().weakened_by(&new.to_core()) {
+                    diff.weakens = true;
+                }
+            }
+            _ => {}
+        }
+
+        // Join rules, any change to a rule can let through members it used to catch
+        let mut matched = vec![false; current.join_rules.len()];
+
+        for rule in self.join_rules.iter() {
+            let old = current
+                .join_rules
+                .iter()
+                .enumerate()
+                .find(|(i, r)| !matched[*i] && r.rule_name == rule.rule_name);
+
+            match old {
+                Some((i, old)) => {
+                    matched[i] = true;
+
+                    if old != rule {
+                        diff.lines.push(format!("~ Join Rule {}", rule.rule_name));
+                        diff.weakens = true;
+                    }
+                }
+                None => diff.lines.push(format!("+ Join Rule {}", rule.rule_name)),
+            }
+        }
+
+        for (i, old) in current.join_rules.iter().enumerate() {
+            if !matched[i] {
+                diff.lines.push(format!("- Join Rule {}", old.rule_name));
+                diff.weakens = true;
+
+                if let Some(id) = current_ids.join_rules.get(i) {
+                    diff.removed_join_rules
+                        .push((id.clone(), old.rule_name.clone()));
+                }
+            }
+        }
+
+        diff
+    }
+
+    /// Replaces the configuration of a guild with this one, run in a transaction so a failed
+    /// import changes nothing
+    ///
+    /// Limits and join rules matched by name are updated in place so their history is kept
+    pub async fn apply(
+        &self,
+        conn: &mut PgConnection,
+        guild_id: GuildId,
+        current: &GuildConfig,
+        current_ids: &ConfigIds,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "
+                UPDATE guilds
+                SET log_channel = $2,
+                quarantine_role = $3,
+                dm_severity = $4,
+                safeguard_mode = $5,
+                safeguard_cooldown = make_interval(secs => $6)
+                WHERE guild_id = $1
+            ",
+            guild_id.to_string(),
+            self.settings.log_channel,
+            self.settings.quarantine_role,
+            self.settings.dm_severity,
+            self.settings.safeguard_mode,
+            self.settings.safeguard_cooldown as f64
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        // Limits
+        let mut matched = vec![false; current.limits.len()];
+
+        for limit in self.limits.iter() {
+            let old = current
+                .limits
+                .iter()
+                .enumerate()
+                .find(|(i, l)| !matched[*i] && l.limit_name == limit.limit_name)
+                .map(|(i, _)| i);
+
+            match old.and_then(|i| current_ids.limits.get(i).map(|id| (i, id))) {
+                Some((i, limit_id)) => {
+                    matched[i] = true;
+
+                    sqlx::query!(
+                        "
+                            UPDATE limits
+                            SET limit_type = $3,
+                            limit_action = $4,
+                            limit_per = $5,
+                            limit_time = make_interval(secs => $6),
+                            limit_targets = $7
+                            WHERE guild_id = $1
+                            AND limit_id = $2
+                        ",
+                        guild_id.to_string(),
+                        limit_id,
+                        limit.limit_type,
+                        limit.limit_action,
+                        limit.limit_per,
+                        limit.limit_time as f64,
+                        &limit.limit_targets
+                    )
+                    .execute(&mut *conn)
+                    .await?;
+                }
+                None => {
+                    sqlx::query!(
+                        "
+                            INSERT INTO limits (
+                                guild_id,
+                                limit_name,
+                                limit_type,
+                                limit_action,
+                                limit_per,
+                                limit_time,
+                                limit_targets
+                            )
+                            VALUES (
+                                $1,
+                                $2,
+                                $3,
+                                $4,
+                                $5,
+                                make_interval(secs => $6),
+                                $7
+                            )
+                        ",
+                        guild_id.to_string(),
+                        limit.limit_name,
+                        limit.limit_type,
+                        limit.limit_action,
+                        limit.limit_per,
+                        limit.limit_time as f64,
+                        &limit.limit_targets
+                    )
+                    .execute(&mut *conn)
+                    .await?;
+                }
+            }
+        }
+
+        for (i, limit_id) in current_ids.limits.iter().enumerate() {
+            if !matched.get(i).copied().unwrap_or(true) {
+                sqlx::query!(
+                    "DELETE FROM limits WHERE guild_id = $1 AND limit_id = $2",
+                    guild_id.to_string(),
+                    limit_id
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+
+        // Everything else is replaced outright
+        sqlx::query!(
+            "DELETE FROM limit_weights WHERE guild_id = $1",
+            guild_id.to_string()
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        for weight in self.weights.iter() {
+            sqlx::query!(
+                "INSERT INTO limit_weights (guild_id, limit_type, weight) VALUES ($1, $2, $3)",
+                guild_id.to_string(),
+                weight.limit_type,
+                weight.weight
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        sqlx::query!(
+            "DELETE FROM guild_admins WHERE guild_id = $1",
+            guild_id.to_string()
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        for admin in self.admins.iter() {
+            sqlx::query!(
+                "INSERT INTO guild_admins (guild_id, user_id) VALUES ($1, $2)",
+                guild_id.to_string(),
+                admin
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        sqlx::query!(
+            "DELETE FROM guild_admin_roles WHERE guild_id = $1",
+            guild_id.to_string()
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        for role_id in self.admin_roles.iter() {
+            sqlx::query!(
+                "INSERT INTO guild_admin_roles (guild_id, role_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                guild_id.to_string(),
+                role_id
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        sqlx::query!(
+            "DELETE FROM guild_whitelist WHERE guild_id = $1",
+            guild_id.to_string()
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        for entry in self.whitelist.iter() {
+            sqlx::query!(
+                "
+                    INSERT INTO guild_whitelist (guild_id, target_id, target_type)
+                    VALUES ($1, $2, $3)
+                    ON CONFLICT DO NOTHING
+                ",
+                guild_id.to_string(),
+                entry.target_id,
+                entry.target_type
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        sqlx::query!(
+            "DELETE FROM guild_capabilities WHERE guild_id = $1",
+            guild_id.to_string()
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        for grant in self.capabilities.iter() {
+            sqlx::query!(
+                "
+                    INSERT INTO guild_capabilities (guild_id, target_id, target_type, capability)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT DO NOTHING
+                ",
+                guild_id.to_string(),
+                grant.target_id,
+                grant.target_type,
+                grant.capability
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        match self.raid {
+            Some(ref raid) => {
+                sqlx::query!(
+                    "
+                        INSERT INTO raid_config (
+                            guild_id,
+                            raid_joins,
+                            raid_time,
+                            raid_action,
+                            raise_verification,
+                            lockdown
+                        )
+                        VALUES (
+                            $1,
+                            $2,
+                            make_interval(secs => $3),
+                            $4,
+                            $5,
+                            $6
+                        )
+                        ON CONFLICT (guild_id) DO UPDATE SET
+                            raid_joins = EXCLUDED.raid_joins,
+                            raid_time = EXCLUDED.raid_time,
+                            raid_action = EXCLUDED.raid_action,
+                            raise_verification = EXCLUDED.raise_verification,
+                            lockdown = EXCLUDED.lockdown
+                    ",
+                    guild_id.to_string(),
+                    raid.raid_joins,
+                    raid.raid_time as f64,
+                    raid.raid_action,
+                    raid.raise_verification,
+                    raid.lockdown
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+            None => {
+                sqlx::query!(
+                    "DELETE FROM raid_config WHERE guild_id = $1",
+                    guild_id.to_string()
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+
+        match self.spam {
+            Some(ref spam) => {
+                sqlx::query!(
+                    "
+                        INSERT INTO spam_config (
+                            guild_id,
+                            spam_time,
+                            spam_duplicates,
+                            spam_channels,
+                            spam_mentions,
+                            spam_invites
+                        )
+                        VALUES (
+                            $1,
+                            make_interval(secs => $2),
+                            $3,
+                            $4,
+                            $5,
+                            $6
+                        )
+                        ON CONFLICT (guild_id) DO UPDATE SET
+                            spam_time = EXCLUDED.spam_time,
+                            spam_duplicates = EXCLUDED.spam_duplicates,
+                            spam_channels = EXCLUDED.spam_channels,
+                            spam_mentions = EXCLUDED.spam_mentions,
+                            spam_invites = EXCLUDED.spam_invites
+                    ",
+                    guild_id.to_string(),
+                    spam.spam_time as f64,
+                    spam.spam_duplicates,
+                    spam.spam_channels,
+                    spam.spam_mentions,
+                    spam.spam_invites
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+            None => {
+                sqlx::query!(
+                    "DELETE FROM spam_config WHERE guild_id = $1",
+                    guild_id.to_string()
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+
+        // Join rules
+        let mut matched = vec![false; current.join_rules.len()];
+
+        for rule in self.join_rules.iter() {
+            let old = current
+                .join_rules
+                .iter()
+                .enumerate()
+                .find(|(i, r)| !matched[*i] && r.rule_name == rule.rule_name)
+                .map(|(i, _)| i);
+
+            match old.and_then(|i| current_ids.join_rules.get(i).map(|id| (i, id))) {
+                Some((i, rule_id)) => {
+                    matched[i] = true;
+
+                    sqlx::query!(
+                        "
+                            UPDATE join_rules
+                            SET rule_action = $3,
+                            min_account_age = make_interval(secs => $4),
+                            default_avatar = $5,
+                            username_pattern = $6
+                            WHERE guild_id = $1
+                            AND rule_id = $2
+                        ",
+                        guild_id.to_string(),
+                        rule_id,
+                        rule.rule_action,
+                        rule.min_account_age.map(|a| a as f64),
+                        rule.default_avatar,
+                        rule.username_pattern
+                    )
+                    .execute(&mut *conn)
+                    .await?;
+                }
+                None => {
+                    sqlx::query!(
+                        "
+                            INSERT INTO join_rules (
+                                guild_id,
+                                rule_name,
+                                rule_action,
+                                min_account_age,
+                                default_avatar,
+                                username_pattern
+                            )
+                            VALUES (
+                                $1,
+                                $2,
+                                $3,
+                                make_interval(secs => $4),
+                                $5,
+                                $6
+                            )
+                        ",
+                        guild_id.to_string(),
+                        rule.rule_name,
+                        rule.rule_action,
+                        rule.min_account_age.map(|a| a as f64),
+                        rule.default_avatar,
+                        rule.username_pattern
+                    )
+                    .execute(&mut *conn)
+                    .await?;
+                }
+            }
+        }
+
+        for (i, rule_id) in current_ids.join_rules.iter().enumerate() {
+            if !matched.get(i).copied().unwrap_or(true) {
+                sqlx::query!(
+                    "DELETE FROM join_rules WHERE guild_id = $1 AND rule_id = $2",
+                    guild_id.to_string(),
+                    rule_id
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Export and import the Skynet configuration of this server
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("config_export", "config_import")
+)]
+pub async fn config(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Export the limits, detection settings, admins, whitelist and settings of this server as YAML
+#[poise::command(prefix_command, slash_command, guild_only, rename = "export")]
+pub async fn config_export(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    let config = GuildConfig::export(&ctx.data().pool, guild_id).await?;
+
+    let yaml = serde_yaml::to_string(&config).map_err(|_| "Could not serialize config")?;
+
+    ctx.send(
+        CreateReply::default()
+            .content("Use ``/config import`` with this file to copy this configuration to another server")
+            .attachment(CreateAttachment::bytes(
+                yaml.into_bytes(),
+                format!("skynet-{}.yaml", guild_id),
+            )),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Import a configuration exported with /config export, previewing the changes first
+#[poise::command(prefix_command, slash_command, guild_only, rename = "import")]
+pub async fn config_import(
+    ctx: Context<'_>,
+    #[description = "The exported YAML or JSON file"] file: Attachment,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    if file.size > MAX_IMPORT_SIZE {
+        return Err("Config file is too large".into());
+    }
+
+    let bytes = file
+        .download()
+        .await
+        .map_err(|_| "Could not download config file")?;
+
+    // JSON is valid YAML, so both formats can be parsed the same way
+    let mut config: GuildConfig = serde_yaml::from_slice(&bytes)
+        .map_err(|e| format!("Could not parse config file: {}", e))?;
+
+    let (current, current_ids) = GuildConfig::export_with_ids(&ctx.data().pool, guild_id).await?;
+
+    let (warnings, owner_id) = {
+        let guild = ctx.guild().ok_or("Could not get guild from cache")?;
+        (config.validate(&guild, &current)?, guild.owner_id)
+    };
+
+    config.keep_missing_sections(&current);
+
+    let diff = config.diff(&current, &current_ids);
+
+    if diff.lines.is_empty() {
+        ctx.say("This server already has this configuration")
+            .await?;
+        return Ok(());
+    }
+
+    if diff.changes_admins && ctx.author().id != owner_id {
+        return Err("Only guild owners can import a config that changes admins".into());
+    }
+
+    let mut preview = format!("```diff\n{}\n```", diff.lines.join("\n"));
+
+    if preview.chars().count() > 3500 {
+        preview = preview.chars().take(3500).collect::<String>() + "\n...```";
+    }
+
+    let mut embed = CreateEmbed::default()
+        .title("Import Configuration?")
+        .description(preview)
+        .color(0x00ff00);
+
+    if !warnings.is_empty() {
+        embed = embed.field("Skipped", warnings.join("\n"), false);
+    }
+
+    let handle = ctx
+        .send(
+            CreateReply::default()
+                .embed(embed)
+                .components(vec![CreateActionRow::Buttons(vec![
+                    CreateButton::new("config_import_apply")
+                        .label("Import")
+                        .style(ButtonStyle::Success),
+                    CreateButton::new("config_import_cancel")
+                        .label("Cancel")
+                        .style(ButtonStyle::Danger),
+                ])]),
+        )
+        .await?;
+
+    let msg_id = handle.message().await?.id;
+
+    let interaction = crate::utils::next_component(ctx, msg_id, 120).await?;
+
+    if !interaction.is_some_and(|i| i.data.custom_id.as_str() == "config_import_apply") {
+        handle
+            .edit(
+                ctx,
+                CreateReply::default()
+                    .content("Configuration not imported")
+                    .components(vec![]),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    // The safeguards explain in their own reply why the import was held back
+    if diff.weakens && !crate::safeguards::allow_weakening(ctx, "import a configuration").await? {
+        handle
+            .edit(
+                ctx,
+                CreateReply::default()
+                    .content("Configuration not imported")
+                    .components(vec![]),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    handle
+        .edit(
+            ctx,
+            CreateReply::default()
+                .content("Importing configuration...")
+                .components(vec![]),
+        )
+        .await?;
+
+    let mut tx = ctx.data().pool.begin().await?;
+
+    config
+        .apply(&mut tx, guild_id, &current, &current_ids)
+        .await?;
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        Some(serde_json::to_value(&current)?),
+        Some(serde_json::to_value(&config)?),
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    ctx.data().spam_configs.invalidate(guild_id);
+
+    for (limit_id, _) in diff.removed_limits {
+        crate::safeguards::record_deletion(ctx, format!("limit:{}", limit_id)).await?;
+    }
+
+    for (rule_id, _) in diff.removed_join_rules {
+        crate::safeguards::record_deletion(ctx, format!("join_rule:{}", rule_id)).await?;
+    }
+
+    if diff.disables_raid {
+        crate::safeguards::record_deletion(ctx, "raid_config".to_string()).await?;
+    }
+
+    if diff.disables_spam {
+        crate::safeguards::record_deletion(ctx, "spam_config".to_string()).await?;
+    }
+
+    handle
+        .edit(
+            ctx,
+            CreateReply::default()
+                .content("Configuration imported successfully")
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> GuildConfig {
+        GuildConfig {
+            version: CONFIG_VERSION,
+            settings: ConfigSettings {
+                log_channel: Some("10".to_string()),
+                dm_severity: Some("medium".to_string()),
+                safeguard_mode: Some("cooldown".to_string()),
+                safeguard_cooldown: 3600,
+                ..Default::default()
+            },
+            limits: vec![ConfigLimit {
+                limit_name: "Bans".to_string(),
+                limit_type: "ban".to_string(),
+                limit_action: "ban_user".to_string(),
+                limit_per: 3,
+                limit_time: 60,
+                limit_targets: vec![],
+            }],
+            weights: vec![],
+            admins: vec![],
+            admin_roles: vec![],
+            whitelist: vec![],
+            raid: Some(ConfigRaid {
+                raid_joins: 10,
+                raid_time: 60,
+                raid_action: "kick_joiners".to_string(),
+                raise_verification: true,
+                lockdown: false,
+            }),
+            spam: Some(ConfigSpam {
+                spam_time: 10,
+                spam_duplicates: 3,
+                spam_channels: 0,
+                spam_mentions: 5,
+                spam_invites: false,
+            }),
+            join_rules: vec![ConfigJoinRule {
+                rule_name: "New Accounts".to_string(),
+                rule_action: "kick_user".to_string(),
+                min_account_age: Some(86400),
+                default_avatar: false,
+                username_pattern: None,
+            }],
+            capabilities: vec![ConfigCapability {
+                target_id: "1".to_string(),
+                target_type: "user".to_string(),
+                capability: "view_actions".to_string(),
+            }],
+        }
+    }
+
+    fn ids() -> ConfigIds {
+        ConfigIds {
+            limits: vec!["limit".to_string()],
+            join_rules: vec!["rule".to_string()],
+        }
+    }
+
+    /// Validates against the base config in a guild with channel 10 and role 20
+    fn validate(new: &mut GuildConfig) -> Result<Vec<String>, Error> {
+        new.validate_with(&config(), |id| id == "10", |id| id == "20")
+    }
+
+    #[test]
+    fn validate_accepts_exported_config() {
+        assert!(validate(&mut config()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_versions() {
+        for version in [0, CONFIG_VERSION + 1] {
+            let mut new = config();
+            new.version = version;
+            assert!(validate(&mut new).is_err());
+        }
+    }
+
+    #[test]
+    fn validate_rejects_invalid_values() {
+        let mut new = config();
+        new.settings.dm_severity = Some("extreme".to_string());
+        assert!(validate(&mut new).is_err());
+
+        let mut new = config();
+        new.settings.safeguard_cooldown = -1;
+        assert!(validate(&mut new).is_err());
+
+        let mut new = config();
+        new.limits[0].limit_per = 0;
+        assert!(validate(&mut new).is_err());
+
+        let mut new = config();
+        new.weights.push(ConfigWeight {
+            limit_type: "threat_score".to_string(),
+            weight: 1,
+        });
+        assert!(validate(&mut new).is_err());
+
+        let mut new = config();
+        new.raid.as_mut().unwrap().raid_joins = 1;
+        assert!(validate(&mut new).is_err());
+
+        let mut new = config();
+        new.spam.as_mut().unwrap().spam_time = 0;
+        assert!(validate(&mut new).is_err());
+
+        let mut new = config();
+        new.join_rules[0].min_account_age = None;
+        assert!(validate(&mut new).is_err());
+
+        let mut new = config();
+        new.capabilities[0].capability = "everything".to_string();
+        assert!(validate(&mut new).is_err());
+    }
+
+    #[test]
+    fn validate_drops_missing_channels_and_roles() {
+        let mut new = config();
+        new.settings.log_channel = Some("11".to_string());
+        new.settings.quarantine_role = Some("20".to_string());
+        new.admin_roles = vec!["20".to_string(), "21".to_string()];
+        new.limits[0].limit_targets = vec!["10".to_string(), "12".to_string()];
+
+        let warnings = validate(&mut new).unwrap();
+
+        assert_eq!(warnings.len(), 3);
+        // Logging stays on with the current channel
+        assert_eq!(new.settings.log_channel, Some("10".to_string()));
+        assert_eq!(new.settings.quarantine_role, Some("20".to_string()));
+        assert_eq!(new.admin_roles, vec!["20".to_string()]);
+        assert_eq!(new.limits[0].limit_targets, vec!["10".to_string()]);
+    }
+
+    #[test]
+    fn validate_rejects_limit_without_remaining_targets() {
+        // Dropping every target would widen the limit to the whole server
+        let mut new = config();
+        new.limits[0].limit_targets = vec!["12".to_string()];
+        assert!(validate(&mut new).is_err());
+    }
+
+    #[test]
+    fn diff_of_same_config_is_empty() {
+        let diff = config().diff(&config(), &ids());
+
+        assert!(diff.lines.is_empty());
+        assert!(!diff.weakens);
+        assert!(!diff.changes_admins);
+    }
+
+    #[test]
+    fn stricter_config_does_not_weaken() {
+        let mut new = config();
+        new.limits[0].limit_per = 2;
+        new.limits[0].limit_time = 120;
+        new.raid.as_mut().unwrap().raid_action = "ban_joiners".to_string();
+        new.spam.as_mut().unwrap().spam_channels = 2;
+        new.settings.safeguard_cooldown = 7200;
+        new.settings.dm_severity = Some("low".to_string());
+        new.weights.push(ConfigWeight {
+            limit_type: "ban".to_string(),
+            weight: 2,
+        });
+        new.limits.push(ConfigLimit {
+            limit_name: "Kicks".to_string(),
+            ..config().limits[0].clone()
+        });
+
+        let diff = new.diff(&config(), &ids());
+
+        assert!(!diff.lines.is_empty());
+        assert!(!diff.weakens);
+    }
+
+    #[test]
+    fn weakening_changes_are_detected() {
+        let weakened: &[fn(&mut GuildConfig)] = &[
+            |c| c.limits[0].limit_per = 4,
+            |c| c.limits[0].limit_time = 30,
+            |c| c.limits[0].limit_action = "kick_user".to_string(),
+            |c| c.limits[0].limit_targets = vec!["10".to_string()],
+            |c| c.limits.clear(),
+            |c| {
+                c.weights.push(ConfigWeight {
+                    limit_type: "ban".to_string(),
+                    weight: 0,
+                })
+            },
+            |c| {
+                c.whitelist.push(ConfigWhitelistEntry {
+                    target_id: "1".to_string(),
+                    target_type: "user".to_string(),
+                })
+            },
+            |c| c.settings.safeguard_mode = None,
+            |c| c.settings.log_channel = None,
+            |c| c.settings.dm_severity = None,
+            |c| c.settings.dm_severity = Some("high".to_string()),
+            |c| c.settings.safeguard_cooldown = 60,
+            |c| c.raid = None,
+            |c| c.raid.as_mut().unwrap().raise_verification = false,
+            |c| c.spam = None,
+            |c| c.spam.as_mut().unwrap().spam_duplicates = 0,
+            |c| c.join_rules[0].min_account_age = Some(3600),
+            |c| c.join_rules.clear(),
+            |c| c.capabilities.clear(),
+        ];
+
+        for (i, weaken) in weakened.iter().enumerate() {
+            let mut new = config();
+            weaken(&mut new);
+
+            let diff = new.diff(&config(), &ids());
+
+            assert!(diff.weakens, "change {} should weaken", i);
+        }
+    }
+
+    #[test]
+    fn removals_are_reported_with_ids() {
+        let mut new = config();
+        new.limits.clear();
+        new.join_rules.clear();
+        new.raid = None;
+
+        let diff = new.diff(&config(), &ids());
+
+        assert_eq!(
+            diff.removed_limits,
+            vec![("limit".to_string(), "Bans".to_string())]
+        );
+        assert_eq!(
+            diff.removed_join_rules,
+            vec![("rule".to_string(), "New Accounts".to_string())]
+        );
+        assert!(diff.disables_raid);
+        assert!(!diff.disables_spam);
+    }
+
+    #[test]
+    fn admin_changes_are_detected() {
+        let mut new = config();
+        new.admins.push("1".to_string());
+        assert!(new.diff(&config(), &ids()).changes_admins);
+
+        let mut new = config();
+        new.capabilities.push(ConfigCapability {
+            target_id: "20".to_string(),
+            target_type: "role".to_string(),
+            capability: "manage_admins".to_string(),
+        });
+        assert!(new.diff(&config(), &ids()).changes_admins);
+
+        let mut new = config();
+        new.capabilities[0].capability = "manage_limits".to_string();
+        assert!(!new.diff(&config(), &ids()).changes_admins);
+    }
+
+    #[test]
+    fn older_configs_keep_missing_sections() {
+        let mut old = config();
+        old.version = 1;
+        old.raid = None;
+        old.spam = None;
+        old.join_rules.clear();
+        old.capabilities.clear();
+
+        old.keep_missing_sections(&config());
+
+        let diff = old.diff(&config(), &ids());
+
+        assert!(diff.lines.is_empty());
+        assert!(!diff.weakens);
+    }
+}
@@ -1,8 +1,47 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
 use log::{error, info, warn};
-use poise::serenity_prelude::{GuildId, UserId};
-use sqlx::PgPool;
+use once_cell::sync::Lazy;
+use poise::serenity_prelude::{
+    ChannelId, CreateEmbed, EditGuild, Guild, GuildId, Member, Mentionable, Message, MessageId,
+    Timestamp, UserId, VerificationLevel,
+};
+use regex::Regex;
+use sqlx::{types::chrono::Utc, PgPool};
+
+use crate::{
+    core,
+    spam::{SpamConfigCache, SpamTracker},
+    webhooks::WebhookEvent,
+    Error,
+};
+
+static INVITE_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(discord\.gg|discord(app)?\.com/invite)/[a-z0-9-]+")
+        .expect("Invalid invite regex")
+});
+
+/// Compiled join rule username patterns, by pattern, so they are not compiled on every join
+static JOIN_RULE_PATTERNS: Lazy<Mutex<HashMap<String, Regex>>> = Lazy::new(Mutex::default);
+
+/// Returns the compiled username pattern of a join rule
+fn join_rule_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut patterns = JOIN_RULE_PATTERNS.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some(re) = patterns.get(pattern) {
+        return Ok(re.clone());
+    }
+
+    let re = Regex::new(pattern)?;
+    patterns.insert(pattern.to_string(), re.clone());
+
+    Ok(re)
+}
 
-use crate::{core, Error};
+/// How long members matching a join rule with the timeout action are timed out for
+const JOIN_RULE_TIMEOUT_SECS: i64 = 86400;
 
 pub async fn handle_mod_action(
     guild_id: GuildId,
@@ -12,50 +51,135 @@ pub async fn handle_mod_action(
     action: core::UserLimitTypes,
     action_target: String,
 ) -> Result<(), Error> {
+    handle_mod_actions(guild_id, user_id, pool, ctx, action, vec![action_target]).await
+}
+
+/// Records one action per target before checking limits once
+pub async fn handle_mod_actions(
+    guild_id: GuildId,
+    user_id: UserId,
+    pool: &PgPool,
+    ctx: &serenity::all::Context,
+    action: core::UserLimitTypes,
+    action_targets: Vec<String>,
+) -> Result<(), Error> {
+    let start = Instant::now();
+    let label = action.to_string();
+
+    let res = check_mod_actions(guild_id, user_id, pool, ctx, action, action_targets).await;
+
+    ctx.data::<crate::Data>()
+        .metrics
+        .mod_action_latency
+        .observe(&label, start.elapsed());
+
+    res
+}
+
+async fn check_mod_actions(
+    guild_id: GuildId,
+    user_id: UserId,
+    pool: &PgPool,
+    ctx: &serenity::all::Context,
+    action: core::UserLimitTypes,
+    action_targets: Vec<String>,
+) -> Result<(), Error> {
+    // Look for guild
+    let guild = sqlx::query!(
+        "
+        SELECT COUNT(*)
+        FROM guilds
+        WHERE guild_id = $1
+    ",
+        guild_id.to_string()
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if guild.count.unwrap_or_default() == 0 {
+        // Guild not found
+        error!("Guild not found: {}", guild_id);
+        return Ok(());
+    }
+
+    // Whitelisted users and roles are trusted, except when tampering with Skynet itself
+    if !matches!(
+        action,
+        core::UserLimitTypes::BotTamper | core::UserLimitTypes::ConfigDelete
+    ) {
+        // A failed check must not let the action through unchecked, so treat it as not whitelisted
+        let whitelisted = crate::utils::is_whitelisted(ctx, pool, guild_id, user_id)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(
+                    "Failed to check whitelist of {} in {}: {}",
+                    user_id, guild_id, e
+                );
+                false
+            });
+
+        if whitelisted {
+            return Ok(());
+        }
+    }
+
+    let data = ctx.data::<crate::Data>();
+    let metrics = &data.metrics;
+
     // SAFETY: Tx should be dropped if error occurs, so make a scope to seperate tx queries
     {
         let mut tx = pool.begin().await?;
 
-        // Look for guild
-        let guild = sqlx::query!(
-            "
-            SELECT COUNT(*)
-            FROM guilds
-            WHERE guild_id = $1
-        ",
-            guild_id.to_string()
-        )
-        .fetch_one(&mut *tx)
-        .await?;
+        // Insert into user_actions, one row per target
+        let start = Instant::now();
 
-        if guild.count.unwrap_or_default() == 0 {
-            // Guild not found
-            error!("Guild not found: {}", guild_id);
-            return Ok(());
-        }
+        let action_ids = action_targets
+            .iter()
+            .map(|_| botox::crypto::gen_random(48))
+            .collect::<Vec<_>>();
 
-        // Insert into user_actions
         sqlx::query!(
             "
             INSERT INTO user_actions (action_id, guild_id, user_id, limit_type, action_target)
-            VALUES ($1, $2, $3, $4, $5)
+            SELECT action_id, $1, $2, $3, action_target
+            FROM UNNEST($4::text[], $5::text[]) AS targets(action_id, action_target)
         ",
-            botox::crypto::gen_random(48),
             guild_id.to_string(),
             user_id.to_string(),
             action.to_string(),
-            action_target
+            &action_ids,
+            &action_targets
         )
         .execute(&mut *tx)
         .await?;
 
-        // Check if they hit any limits yet
-        let hit = core::CurrentUserLimitsHit::hit(guild_id, pool).await?;
+        metrics
+            .db_query_latency
+            .observe("record_action", start.elapsed());
+
+        // Check if they hit any limits yet, counting the actions inserted above
+        metrics.limit_evaluations.inc(&action.to_string());
+
+        let start = Instant::now();
+        let hit = core::CurrentUserLimitsHit::hit(guild_id, user_id, pool, &mut tx).await?;
+        metrics
+            .db_query_latency
+            .observe("check_limits", start.elapsed());
+
+        // Alerts are only sent once the hits are committed
+        let mut alerts = Vec::new();
 
         for hit_limit in hit {
             // We have a hit limit for this user
             info!("Hit limit: {:?}", hit_limit);
 
+            metrics
+                .limit_hits
+                .inc(&hit_limit.limit.limit_type.to_string());
+
+            // Deleting messages does not need a higher role than the user
+            delete_cause_messages(ctx, &hit_limit.cause).await;
+
             // Immediately handle the limit
             let cur_uid = ctx.cache.current_user().id;
             let can_mod = {
@@ -65,96 +189,734 @@ pub async fn handle_mod_action(
             }
             .unwrap_or(cur_uid);
 
-            if can_mod == cur_uid {
+            // Lockdowns do not act on the user, so they can always be applied
+            let needs_hierarchy = hit_limit.limit.limit_action != core::UserLimitActions::Lockdown;
+
+            let res: Result<(), Error> = if !needs_hierarchy || can_mod == cur_uid {
                 info!("Moderating user");
-                match hit_limit.limit.limit_action {
+                let res: Result<(), Error> = match hit_limit.limit.limit_action {
                     core::UserLimitActions::RemoveAllRoles => {
-                        // Get all user roles
-                        if let Ok(member) = guild_id.member(&ctx, user_id).await {
-                            let roles = member.roles.clone();
-                            for role in roles.iter() {
-                                if let Err(e) = member.remove_role(
-                                    &ctx.http, 
-                                    *role, 
-                                    Some("Removing roles due to preconfigured limits being hit")
-                                ).await {
-                                    error!("Failed to remove role: {}", e);
-                                }
-                            }
-                        }
+                        remove_all_roles(ctx, guild_id, user_id, "Removing roles due to preconfigured limits being hit").await
                     }
                     core::UserLimitActions::KickUser => {
-                        if let Err(e) = guild_id.kick(&ctx.http, user_id, Some("Configured limit has been hit")).await {
-                            error!("Failed to kick user: {}", e);
-                        }
+                        guild_id.kick(&ctx.http, user_id, Some("Configured limit has been hit")).await.map_err(|e| e.into())
                     }
                     core::UserLimitActions::BanUser => {
-                        if let Err(e) = guild_id.ban(&ctx.http, user_id, 0, Some("Configured limit has been hit")).await {
-                            error!("Failed to kick user: {}", e);
-                        }
+                        guild_id.ban(&ctx.http, user_id, 0, Some("Configured limit has been hit")).await.map_err(|e| e.into())
                     }
-                }
+                    core::UserLimitActions::Lockdown => {
+                        crate::lockdown::start_lockdown(ctx, pool, guild_id, false, "Configured limit has been hit").await.map(|_| ())
+                    }
+                };
+
+                metrics
+                    .punishments
+                    .inc(if res.is_err() { "failed" } else { "succeeded" });
+
+                res
             } else {
+                metrics.punishments.inc("no_permission");
+
                 warn!(
                     "Cannot moderate user, not enough permissions: {}, {}",
                     can_mod, cur_uid
                 );
 
-                sqlx::query!(
-                    "
-                INSERT INTO past_hit_limits
-                (id, guild_id, user_id, limit_id, cause, notes)
-                VALUES ($1, $2, $3, $4, $5, $6)",
-                    botox::crypto::gen_random(16),
-                    guild_id.to_string(),
-                    user_id.to_string(),
-                    hit_limit.limit.limit_id,
-                    &hit_limit
-                        .cause
-                        .iter()
-                        .map(|a| a.action_id.clone())
-                        .collect::<Vec<_>>(),
-                    &vec!["Not enough permissions to moderate user".to_string()]
-                )
-                .execute(&mut *tx)
-                .await?;
+                Err("Not enough permissions to moderate user".into())
+            };
 
-                return Ok(());
+            let mut notes = Vec::new();
+
+            if let Err(e) = &res {
+                error!("Failed to punish user: {}", e);
+                notes.push(format!(
+                    "Failed to {}: {}",
+                    hit_limit.limit.limit_action.to_cond(),
+                    e
+                ));
             }
 
-            for action in hit_limit.cause.iter() {
-                sqlx::query!(
-                    "
+            let cause_ids = hit_limit
+                .cause
+                .iter()
+                .map(|a| a.action_id.clone())
+                .collect::<Vec<_>>();
+
+            // Causes are marked as handled even if the punishment failed, so the same actions
+            // do not produce a new hit, webhook and DM on every following action
+            sqlx::query!(
+                "
                 UPDATE user_actions
                 SET limits_hit = array_append(limits_hit, $1)
-                WHERE action_id = $2",
-                    hit_limit.limit.limit_id,
-                    action.action_id
-                )
-                .execute(&mut *tx)
-                .await?;
-            }
+                WHERE action_id = ANY($2)",
+                hit_limit.limit.limit_id,
+                &cause_ids
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            let hit_id = botox::crypto::gen_random(16);
+
+            let start = Instant::now();
 
             sqlx::query!(
                 "
-            INSERT INTO past_hit_limits
-            (id, guild_id, user_id, limit_id, cause)
-            VALUES ($1, $2, $3, $4, $5)",
-                botox::crypto::gen_random(16),
+                INSERT INTO past_hit_limits
+                (id, guild_id, user_id, limit_id, cause, notes, punishment_failed)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                hit_id,
                 guild_id.to_string(),
                 user_id.to_string(),
                 hit_limit.limit.limit_id,
-                &hit_limit
-                    .cause
-                    .iter()
-                    .map(|a| a.action_id.clone())
-                    .collect::<Vec<_>>()
+                &cause_ids,
+                &notes,
+                res.is_err()
             )
             .execute(&mut *tx)
             .await?;
+
+            metrics
+                .db_query_latency
+                .observe("record_hit", start.elapsed());
+
+            alerts.push((hit_id, hit_limit, notes, res.err().map(|e| e.to_string())));
+        }
+
+        tx.commit().await?;
+
+        for (hit_id, hit_limit, notes, failure) in alerts {
+            fire_hit_webhooks(
+                pool,
+                guild_id,
+                user_id,
+                hit_id,
+                &hit_limit,
+                notes,
+                failure.is_some(),
+            )
+            .await;
+
+            crate::notify::notify_hit(ctx, pool, guild_id, user_id, &hit_limit.limit, failure)
+                .await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes every role of a member, failing if any of them could not be removed
+async fn remove_all_roles(
+    ctx: &serenity::all::Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    reason: &str,
+) -> Result<(), Error> {
+    let member = guild_id.member(&ctx, user_id).await?;
+
+    let mut failed = 0;
+
+    for role in member.roles.iter() {
+        if let Err(e) = member.remove_role(&ctx.http, *role, Some(reason)).await {
+            error!("Failed to remove role: {}", e);
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("Could not remove {} of {} roles", failed, member.roles.len()).into());
+    }
+
+    Ok(())
+}
+
+/// Sends a hit limit to the webhooks of the guild, followed by the outcome of its punishment
+async fn fire_hit_webhooks(
+    pool: &PgPool,
+    guild_id: GuildId,
+    user_id: UserId,
+    hit_id: String,
+    hit_limit: &core::CurrentUserLimitsHit,
+    notes: Vec<String>,
+    failed: bool,
+) {
+    let past_hit = core::PastHitLimits {
+        id: hit_id,
+        user_id,
+        guild_id,
+        limit_id: hit_limit.limit.limit_id.clone(),
+        limit_name: hit_limit.limit.limit_name.clone(),
+        cause: hit_limit.cause.clone(),
+        notes,
+        punishment_failed: failed,
+        created_at: Utc::now(),
+    };
+
+    crate::webhooks::fire(pool, guild_id, WebhookEvent::LimitHit, &past_hit).await;
+
+    let event = if failed {
+        WebhookEvent::PunishmentFailed
+    } else {
+        WebhookEvent::PunishmentSucceeded
+    };
+
+    crate::webhooks::fire(pool, guild_id, event, &past_hit).await;
+}
+
+/// Alerts the guild owner and log channel that Skynet has been tampered with before
+/// treating it as an immediate limit hit
+///
+/// Guilds without a ``Skynet Tampering`` limit still have the roles of the user removed,
+/// as tampering stops Skynet from enforcing every other limit
+pub async fn handle_bot_tamper(
+    guild_id: GuildId,
+    user_id: UserId,
+    pool: &PgPool,
+    ctx: &serenity::all::Context,
+    description: String,
+    action_target: String,
+) -> Result<(), Error> {
+    warn!("Skynet tampered with in guild {} by {}: {}", guild_id, user_id, description);
+
+    let limited = core::Limit::from_guild(pool, guild_id)
+        .await?
+        .iter()
+        .any(|l| l.limit_type == core::UserLimitTypes::BotTamper);
+
+    let (owner_id, can_mod) = {
+        let guild = ctx.cache.guild(guild_id).ok_or("Guild not found")?;
+        let cur_uid = ctx.cache.current_user().id;
+
+        (
+            guild.owner_id,
+            guild
+                .greater_member_hierarchy(cur_uid, user_id)
+                .unwrap_or(cur_uid)
+                == cur_uid,
+        )
+    };
+
+    let outcome = if limited {
+        "Handled by the ``Skynet Tampering`` limits of this server".to_string()
+    } else if !can_mod {
+        "Could not remove the roles of this user, they are not below Skynet".to_string()
+    } else {
+        match remove_all_roles(ctx, guild_id, user_id, "Tampering with Skynet").await {
+            Ok(()) => "Removed all roles of this user".to_string(),
+            Err(e) => format!("Failed to remove all roles of this user: {}", e),
+        }
+    };
+
+    let embed = CreateEmbed::default()
+        .title("Skynet Tampering Detected")
+        .description(format!("{} ({}) {}", user_id.mention(), user_id, description))
+        .field("Action Taken", outcome, false)
+        .field(
+            "Why does this matter?",
+            "Skynet can only punish members below its highest role and needs its permissions to enforce limits. Add a ``Skynet Tampering`` limit to choose how this is punished",
+            false,
+        )
+        .color(0xff0000);
+
+    // Alerting should not stop the user from being punished
+    if let Err(e) = crate::utils::send_dm(ctx, owner_id, embed.clone()).await {
+        error!("Failed to DM guild owner: {}", e);
+    }
+
+    if let Err(e) = crate::utils::send_log(ctx, pool, guild_id, embed).await {
+        error!("Failed to send log: {}", e);
+    }
+
+    handle_mod_action(
+        guild_id,
+        user_id,
+        pool,
+        ctx,
+        core::UserLimitTypes::BotTamper,
+        action_target,
+    )
+    .await
+}
+
+/// Alerts the guild owner that Skynet was removed from their guild
+///
+/// The remover cannot be punished or looked up in the audit log anymore, and the log channel
+/// cannot be posted to, so only the webhooks of the guild and a DM to the owner are left
+pub async fn handle_bot_removal(
+    guild: &Guild,
+    pool: &PgPool,
+    ctx: &serenity::all::Context,
+) -> Result<(), Error> {
+    warn!("Skynet removed from guild {}", guild.id);
+
+    crate::webhooks::fire(
+        pool,
+        guild.id,
+        WebhookEvent::BotRemoved,
+        &serde_json::json!({ "guild_name": guild.name }),
+    )
+    .await;
+
+    let embed = CreateEmbed::default()
+        .title("Skynet Removed")
+        .description(format!(
+            "Skynet was removed from **{}** and can no longer protect it. If this was not you, your server may be under attack",
+            guild.name
+        ))
+        .color(0xff0000);
+
+    crate::utils::send_dm(ctx, guild.owner_id, embed).await
+}
+
+/// Deletes the messages targeted by message based actions
+async fn delete_cause_messages(ctx: &serenity::all::Context, cause: &[core::Action]) {
+    let mut targets = cause
+        .iter()
+        .filter(|a| a.limit_type.is_message())
+        .map(|a| a.action_target.as_str())
+        .collect::<Vec<_>>();
+
+    // A single message can be the target of multiple actions
+    targets.sort_unstable();
+    targets.dedup();
+
+    for target in targets {
+        let Some((channel_id, message_id)) = target.split_once('/') else {
+            continue;
+        };
+
+        let (Ok(channel_id), Ok(message_id)) = (
+            channel_id.parse::<ChannelId>(),
+            message_id.parse::<MessageId>(),
+        ) else {
+            continue;
+        };
+
+        if let Err(e) = ctx
+            .http
+            .delete_message(channel_id, message_id, Some("Configured limit has been hit"))
+            .await
+        {
+            error!("Failed to delete message: {}", e);
+        }
+    }
+}
+
+pub async fn handle_message(
+    message: &Message,
+    pool: &PgPool,
+    ctx: &serenity::all::Context,
+    spam_tracker: &SpamTracker,
+    spam_configs: &SpamConfigCache,
+) -> Result<(), Error> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+
+    if message.author.bot {
+        return Ok(());
+    }
+
+    let mentions = message.mentions.len()
+        + message.mention_roles.len()
+        + usize::from(message.mention_everyone);
+
+    if mentions > 0 {
+        let limits = core::Limit::from_guild(pool, guild_id).await?;
+
+        // Most mentions are harmless, so only record them if they are limited
+        if limits
+            .iter()
+            .any(|l| l.limit_type == core::UserLimitTypes::MassMention)
+        {
+            // Every mention is its own action so limits count mentions instead of messages
+            handle_mod_actions(
+                guild_id,
+                message.author.id,
+                pool,
+                ctx,
+                core::UserLimitTypes::MassMention,
+                vec![format!("{}/{}", message.channel_id, message.id); mentions],
+            )
+            .await?;
+        }
+    }
+
+    if message.content.is_empty() {
+        return Ok(());
+    }
+
+    let Some(spam_config) = spam_configs.get(pool, guild_id).await? else {
+        // Spam detection is not enabled
+        return Ok(());
+    };
+
+    let interval = std::time::Duration::from_secs(
+        crate::utils::pg_interval_to_secs(&spam_config.spam_time)
+            .try_into()
+            .unwrap_or_default(),
+    );
+
+    let repeats = spam_tracker.track(
+        guild_id,
+        message.author.id,
+        message.channel_id,
+        &message.content,
+        interval,
+    );
+
+    let mut reasons = Vec::new();
+
+    if spam_config.spam_duplicates > 0 && repeats.duplicates > spam_config.spam_duplicates as usize {
+        reasons.push(format!("{} identical messages", repeats.duplicates));
+    }
+
+    if spam_config.spam_channels > 0 && repeats.channels > spam_config.spam_channels as usize {
+        reasons.push(format!("same message in {} channels", repeats.channels));
+    }
+
+    if spam_config.spam_mentions > 0 && mentions > spam_config.spam_mentions as usize {
+        reasons.push(format!("{} mentions", mentions));
+    }
+
+    if spam_config.spam_invites && INVITE_REGEX.is_match(&message.content) {
+        reasons.push("invite link".to_string());
+    }
+
+    if reasons.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        "Spam by {} in guild {}: {}",
+        message.author.id,
+        guild_id,
+        reasons.join(", ")
+    );
+
+    handle_mod_action(
+        guild_id,
+        message.author.id,
+        pool,
+        ctx,
+        core::UserLimitTypes::MessageSpam,
+        format!("{}/{}", message.channel_id, message.id),
+    )
+    .await
+}
+
+pub async fn handle_join_rules(
+    member: &Member,
+    pool: &PgPool,
+    ctx: &serenity::all::Context,
+) -> Result<(), Error> {
+    let guild_id = member.guild_id;
+
+    let rules = core::JoinRule::from_guild(pool, guild_id).await?;
+
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    // Account age is derived from the snowflake timestamp of the user
+    let account_age = Utc::now().timestamp() - member.user.id.created_at().unix_timestamp();
+
+    for rule in rules {
+        let mut notes = Vec::new();
+
+        if let Some(min_account_age) = rule.min_account_age {
+            if account_age >= crate::utils::pg_interval_to_secs(&min_account_age) {
+                continue;
+            }
+
+            notes.push(format!(
+                "Account is younger than {}",
+                crate::utils::parse_pg_interval(min_account_age)
+            ));
+        }
+
+        if rule.default_avatar {
+            if member.user.avatar.is_some() {
+                continue;
+            }
+
+            notes.push("Account has a default avatar".to_string());
+        }
+
+        if let Some(pattern) = rule.username_pattern {
+            // Patterns are checked when rules are added, but one bad rule must not stop the others
+            let re = match join_rule_pattern(&pattern) {
+                Ok(re) => re,
+                Err(e) => {
+                    warn!(
+                        "Invalid username pattern in join rule {} in guild {}: {}",
+                        rule.rule_id, guild_id, e
+                    );
+                    continue;
+                }
+            };
+
+            let matches = re.is_match(&member.user.name)
+                || member
+                    .user
+                    .global_name
+                    .as_deref()
+                    .map(|n| re.is_match(n))
+                    .unwrap_or_default();
+
+            if !matches {
+                continue;
+            }
+
+            notes.push(format!("Username matches ``{}``", pattern));
+        }
+
+        info!(
+            "Member {} matched join rule {} in guild {}",
+            member.user.id, rule.rule_id, guild_id
+        );
+
+        let res: Result<(), Error> = match rule.rule_action {
+            core::JoinRuleActions::KickUser => guild_id
+                .kick(&ctx.http, member.user.id, Some("Matched a configured join rule"))
+                .await
+                .map_err(|e| e.into()),
+            core::JoinRuleActions::TimeoutUser => {
+                match Timestamp::from_unix_timestamp(Utc::now().timestamp() + JOIN_RULE_TIMEOUT_SECS) {
+                    Ok(until) => member
+                        .clone()
+                        .disable_communication_until_datetime(ctx, until)
+                        .await
+                        .map_err(|e| e.into()),
+                    Err(_) => Err("Could not compute timeout".into()),
+                }
+            }
+            core::JoinRuleActions::QuarantineUser => {
+                match crate::utils::quarantine_role(pool, guild_id).await {
+                    Ok(Some(role)) => member
+                        .add_role(&ctx.http, role, Some("Matched a configured join rule"))
+                        .await
+                        .map_err(|e| e.into()),
+                    Ok(None) => Err("No quarantine role is set, use /settings quarantine_role".into()),
+                    Err(e) => Err(e),
+                }
+            }
+        };
+
+        if let Err(e) = res {
+            error!("Failed to apply join rule: {}", e);
+            notes.push(format!("Failed to {}: {}", rule.rule_action.to_cond(), e));
+        }
+
+        sqlx::query!(
+            "
+            INSERT INTO join_actions (action_id, guild_id, user_id, rule_id, rule_action, notes)
+            VALUES ($1, $2, $3, $4, $5, $6)
+        ",
+            botox::crypto::gen_random(48),
+            guild_id.to_string(),
+            member.user.id.to_string(),
+            rule.rule_id,
+            rule.rule_action.to_string(),
+            &notes
+        )
+        .execute(pool)
+        .await?;
+
+        // Only the first matching rule is applied
+        break;
+    }
+
+    Ok(())
+}
+
+pub async fn handle_member_join(
+    guild_id: GuildId,
+    user_id: UserId,
+    pool: &PgPool,
+    ctx: &serenity::all::Context,
+) -> Result<(), Error> {
+    let Some(raid_config) = core::RaidConfig::from_guild(pool, guild_id).await? else {
+        // Raid detection is not enabled
+        return Ok(());
+    };
+
+    // SAFETY: Tx should be dropped if error occurs, so make a scope to seperate tx queries
+    let (joiners, new_raid) = {
+        let mut tx = pool.begin().await?;
+
+        // Forget joins that are too old to be part of a raid
+        sqlx::query!(
+            "
+            DELETE FROM member_joins
+            WHERE guild_id = $1
+            AND NOW() - joined_at > $2
+        ",
+            guild_id.to_string(),
+            raid_config.raid_time
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO member_joins (guild_id, user_id) VALUES ($1, $2)",
+            guild_id.to_string(),
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let joins = sqlx::query!(
+            "SELECT user_id, handled FROM member_joins WHERE guild_id = $1",
+            guild_id.to_string()
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if joins.len() < raid_config.raid_joins as usize {
+            tx.commit().await?;
+            return Ok(());
         }
 
+        sqlx::query!(
+            "UPDATE member_joins SET handled = true WHERE guild_id = $1 AND NOT handled",
+            guild_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
         tx.commit().await?;
+
+        // If no join has been handled yet, this join started the raid
+        let new_raid = joins.iter().all(|j| !j.handled);
+
+        let mut joiners = Vec::new();
+
+        for join in joins {
+            if !join.handled {
+                joiners.push(join.user_id.parse::<UserId>()?);
+            }
+        }
+
+        (joiners, new_raid)
+    };
+
+    info!(
+        "Raid in guild {}: handling {} joins (new raid: {})",
+        guild_id,
+        joiners.len(),
+        new_raid
+    );
+
+    let mut notes = Vec::new();
+
+    if new_raid && raid_config.raise_verification {
+        let verification_level = ctx
+            .cache
+            .guild(guild_id)
+            .ok_or("Guild not found")?
+            .verification_level;
+
+        if verification_level != VerificationLevel::Higher
+            && verification_level != VerificationLevel::Highest
+        {
+            match guild_id
+                .edit(
+                    ctx,
+                    EditGuild::new()
+                        .verification_level(VerificationLevel::Higher)
+                        .audit_log_reason("Raid detected"),
+                )
+                .await
+            {
+                Ok(_) => notes.push("Raised verification level".to_string()),
+                Err(e) => {
+                    error!("Failed to raise verification level: {}", e);
+                    notes.push(format!("Failed to raise verification level: {}", e));
+                }
+            }
+        }
+    }
+
+    if new_raid && raid_config.lockdown {
+        match crate::lockdown::start_lockdown(ctx, pool, guild_id, false, "Raid detected").await {
+            Ok(true) => notes.push("Started a lockdown".to_string()),
+            Ok(false) => notes.push("Server is already in lockdown".to_string()),
+            Err(e) => {
+                error!("Failed to start lockdown: {}", e);
+                notes.push(format!("Failed to start lockdown: {}", e));
+            }
+        }
+    }
+
+    let mut failed = 0;
+
+    for joiner in joiners.iter() {
+        let res = match raid_config.raid_action {
+            core::RaidActions::None => Ok(()),
+            core::RaidActions::KickJoiners => {
+                guild_id
+                    .kick(&ctx.http, *joiner, Some("Joined during a raid"))
+                    .await
+            }
+            core::RaidActions::BanJoiners => {
+                guild_id
+                    .ban(&ctx.http, *joiner, 0, Some("Joined during a raid"))
+                    .await
+            }
+        };
+
+        if let Err(e) = res {
+            error!("Failed to handle raid joiner {}: {}", joiner, e);
+            failed += 1;
+        }
+    }
+
+    if raid_config.raid_action != core::RaidActions::None {
+        notes.push(format!(
+            "{}: {} succeeded, {} failed",
+            raid_config.raid_action.to_cond(),
+            joiners.len() - failed,
+            failed
+        ));
+    }
+
+    // Only log the start of a raid, later joins are handled silently
+    if new_raid {
+        let mut mentions = joiners
+            .iter()
+            .take(40)
+            .map(|j| j.mention().to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if joiners.len() > 40 {
+            mentions.push_str(&format!(" and {} more", joiners.len() - 40));
+        }
+
+        let embed = CreateEmbed::default()
+            .title("Raid Detected")
+            .description(format!(
+                "{} members joined within {}",
+                joiners.len(),
+                crate::utils::parse_pg_interval(raid_config.raid_time)
+            ))
+            .field(
+                "Actions Taken",
+                if notes.is_empty() {
+                    "None".to_string()
+                } else {
+                    notes
+                        .iter()
+                        .map(|n| format!("- {}", n))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                },
+                false,
+            )
+            .field("Joiners", mentions, false)
+            .color(0xff0000);
+
+        crate::utils::send_log(ctx, pool, guild_id, embed).await?;
     }
 
     Ok(())
@@ -0,0 +1,221 @@
+use poise::serenity_prelude::{CreateEmbed, Guild, Member, Permissions, RoleId, UserId};
+use poise::CreateReply;
+use serenity::prelude::Mentionable;
+
+use crate::core::Limit;
+
+type Error = crate::Error;
+type Context<'a> = crate::Context<'a>;
+
+/// Permissions Skynet needs to record actions and enforce limits
+const REQUIRED_PERMISSIONS: Permissions = Permissions::VIEW_AUDIT_LOG
+    .union(Permissions::BAN_MEMBERS)
+    .union(Permissions::KICK_MEMBERS)
+    .union(Permissions::MANAGE_ROLES)
+    .union(Permissions::MANAGE_MESSAGES);
+
+/// Permissions that make a member a moderator Skynet should be able to act on
+const MODERATOR_PERMISSIONS: Permissions = Permissions::ADMINISTRATOR
+    .union(Permissions::BAN_MEMBERS)
+    .union(Permissions::KICK_MEMBERS)
+    .union(Permissions::MANAGE_ROLES)
+    .union(Permissions::MANAGE_CHANNELS)
+    .union(Permissions::MANAGE_GUILD);
+
+/// A problem that stops Skynet from protecting a guild
+pub struct HealthReport {
+    /// Required permissions Skynet does not have
+    pub missing_permissions: Permissions,
+    /// Moderators Skynet cannot act on, and why
+    pub untouchable: Vec<(UserId, String)>,
+    /// Limits whose action Skynet cannot currently take: (name, id, missing permissions)
+    pub broken_limits: Vec<(String, String, Permissions)>,
+}
+
+impl HealthReport {
+    /// Inspects the cached guild for problems with Skynets role and permissions
+    pub fn check(guild: &Guild, bot_id: UserId, limits: &[Limit]) -> Result<Self, Error> {
+        let bot = guild
+            .members
+            .get(&bot_id)
+            .ok_or("Could not find Skynet in the guild")?;
+
+        let highest_position = |member: &Member| {
+            member
+                .roles
+                .iter()
+                .filter_map(|r| guild.roles.get(r))
+                .map(|r| r.position)
+                .max()
+                .unwrap_or_default()
+        };
+
+        let permissions = |member: &Member| {
+            if member.user.id == guild.owner_id {
+                return Permissions::all();
+            }
+
+            // The @everyone role shares its ID with the guild
+            let mut perms = guild
+                .roles
+                .get(&RoleId::new(guild.id.get()))
+                .map(|r| r.permissions)
+                .unwrap_or(Permissions::empty());
+
+            for role in member.roles.iter() {
+                if let Some(role) = guild.roles.get(role) {
+                    perms |= role.permissions;
+                }
+            }
+
+            if perms.contains(Permissions::ADMINISTRATOR) {
+                Permissions::all()
+            } else {
+                perms
+            }
+        };
+
+        let bot_position = highest_position(bot);
+        let bot_permissions = permissions(bot);
+
+        let mut untouchable = Vec::new();
+
+        for member in guild.members.values() {
+            // The owner can never be punished, so is not worth reporting
+            if member.user.id == bot_id || member.user.id == guild.owner_id || member.user.bot {
+                continue;
+            }
+
+            let member_permissions = permissions(member);
+
+            if !member_permissions.intersects(MODERATOR_PERMISSIONS) {
+                continue;
+            }
+
+            if highest_position(member) >= bot_position {
+                untouchable.push((member.user.id, "Role is not below Skynet".to_string()));
+            } else if !bot_permissions.contains(member_permissions) {
+                untouchable.push((
+                    member.user.id,
+                    format!(
+                        "Has permissions Skynet lacks: {}",
+                        member_permissions.difference(bot_permissions)
+                    ),
+                ));
+            }
+        }
+
+        let mut broken_limits = Vec::new();
+
+        for limit in limits {
+            let missing = limit
+                .limit_action
+                .required_permissions()
+                .difference(bot_permissions);
+
+            if !missing.is_empty() {
+                broken_limits.push((limit.limit_name.clone(), limit.limit_id.clone(), missing));
+            }
+        }
+
+        Ok(Self {
+            missing_permissions: REQUIRED_PERMISSIONS.difference(bot_permissions),
+            untouchable,
+            broken_limits,
+        })
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.missing_permissions.is_empty()
+            && self.untouchable.is_empty()
+            && self.broken_limits.is_empty()
+    }
+
+    pub fn to_embed(&self) -> CreateEmbed<'static> {
+        let mut embed = CreateEmbed::default().title("Skynet Health Check");
+
+        if self.is_healthy() {
+            return embed
+                .description("Skynet can act on every moderator and enforce every limit")
+                .color(0x00ff00);
+        }
+
+        embed = embed
+            .description("Skynet may not be able to protect this server. Move the Skynet role above all moderators and grant it the missing permissions")
+            .color(0xff0000);
+
+        if !self.missing_permissions.is_empty() {
+            embed = embed.field(
+                "Missing Permissions",
+                self.missing_permissions.to_string(),
+                false,
+            );
+        }
+
+        if !self.untouchable.is_empty() {
+            let mut untouchable = self
+                .untouchable
+                .iter()
+                .take(15)
+                .map(|(user_id, reason)| format!("- {}: {}", user_id.mention(), reason))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            if self.untouchable.len() > 15 {
+                untouchable.push_str(&format!("\nand {} more", self.untouchable.len() - 15));
+            }
+
+            embed = embed.field(
+                "Moderators Skynet Cannot Punish",
+                truncate_field(untouchable),
+                false,
+            );
+        }
+
+        if !self.broken_limits.is_empty() {
+            embed = embed.field(
+                "Limits That Cannot Be Enforced",
+                truncate_field(
+                    self.broken_limits
+                        .iter()
+                        .map(|(name, id, missing)| {
+                            format!("- {} [{}]: missing {}", name, id, missing)
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+                false,
+            );
+        }
+
+        embed
+    }
+}
+
+/// Truncates an embed field value to the 1024 characters Discord allows
+fn truncate_field(value: String) -> String {
+    if value.chars().count() > 1024 {
+        value.chars().take(1020).collect::<String>() + "..."
+    } else {
+        value
+    }
+}
+
+/// Check whether Skynet can protect this server
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn health(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    let limits = Limit::from_guild(&ctx.data().pool, guild_id).await?;
+
+    let embed = {
+        let bot_id = ctx.cache().current_user().id;
+        let guild = ctx.guild().ok_or("Could not get guild from cache")?;
+
+        HealthReport::check(&guild, bot_id, &limits)?.to_embed()
+    };
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
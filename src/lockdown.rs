@@ -0,0 +1,174 @@
+use log::{error, info};
+use poise::serenity_prelude::{EditRole, GuildId, Permissions, RoleId};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::Error;
+
+/// Permissions removed from @everyone while a guild is in lockdown
+const LOCKDOWN_PERMISSIONS: Permissions =
+    Permissions::SEND_MESSAGES.union(Permissions::CREATE_INSTANT_INVITE);
+
+/// Guild feature Discord uses to pause invites
+const INVITES_DISABLED: &str = "INVITES_DISABLED";
+
+/// Removes the lockdown permissions from @everyone, storing the previous permissions
+/// so they can be restored once the lockdown ends
+///
+/// Returns false if the guild is already in lockdown
+pub async fn start_lockdown(
+    ctx: &serenity::all::Context,
+    pool: &PgPool,
+    guild_id: GuildId,
+    pause_invites: bool,
+    reason: &str,
+) -> Result<bool, Error> {
+    // The @everyone role shares its ID with the guild
+    let everyone = RoleId::new(guild_id.get());
+
+    let (permissions, mut features) = {
+        let guild = ctx.cache.guild(guild_id).ok_or("Guild not found")?;
+
+        (
+            guild
+                .roles
+                .get(&everyone)
+                .ok_or("Could not find @everyone role")?
+                .permissions,
+            guild
+                .features
+                .iter()
+                .map(|f| f.to_string())
+                .collect::<Vec<String>>(),
+        )
+    };
+
+    // Invites that were already paused are left alone when the lockdown ends
+    let pause_invites = pause_invites && !features.iter().any(|f| f == INVITES_DISABLED);
+
+    // The permissions to restore are saved before anything is changed, so they are
+    // never lost if one of the changes below fails
+    let res = sqlx::query!(
+        "
+        INSERT INTO lockdowns (guild_id, everyone_permissions, invites_paused)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (guild_id) DO NOTHING
+    ",
+        guild_id.to_string(),
+        permissions.bits() as i64,
+        pause_invites
+    )
+    .execute(pool)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    if let Err(e) = guild_id
+        .edit_role(
+            ctx,
+            everyone,
+            EditRole::new()
+                .permissions(permissions.difference(LOCKDOWN_PERMISSIONS))
+                .audit_log_reason(reason),
+        )
+        .await
+    {
+        // Nothing was changed, so the guild is not in lockdown
+        sqlx::query!(
+            "DELETE FROM lockdowns WHERE guild_id = $1",
+            guild_id.to_string()
+        )
+        .execute(pool)
+        .await?;
+
+        return Err(e.into());
+    }
+
+    if pause_invites {
+        features.push(INVITES_DISABLED.to_string());
+
+        if let Err(e) = ctx
+            .http
+            .edit_guild(guild_id, &json!({ "features": features }), Some(reason))
+            .await
+        {
+            error!("Failed to pause invites in guild {}: {}", guild_id, e);
+
+            // Ending the lockdown must not resume invites it never paused
+            sqlx::query!(
+                "UPDATE lockdowns SET invites_paused = false WHERE guild_id = $1",
+                guild_id.to_string()
+            )
+            .execute(pool)
+            .await?;
+        }
+    }
+
+    info!("Started lockdown in guild {}", guild_id);
+
+    Ok(true)
+}
+
+/// Restores the permissions @everyone had before the lockdown started, resuming
+/// invites if the lockdown paused them
+///
+/// Returns false if the guild is not in lockdown
+pub async fn end_lockdown(
+    ctx: &serenity::all::Context,
+    pool: &PgPool,
+    guild_id: GuildId,
+    reason: &str,
+) -> Result<bool, Error> {
+    let Some(rec) = sqlx::query!(
+        "SELECT everyone_permissions, invites_paused FROM lockdowns WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(false);
+    };
+
+    guild_id
+        .edit_role(
+            ctx,
+            RoleId::new(guild_id.get()),
+            EditRole::new()
+                .permissions(Permissions::from_bits_truncate(
+                    rec.everyone_permissions as u64,
+                ))
+                .audit_log_reason(reason),
+        )
+        .await?;
+
+    if rec.invites_paused {
+        let features = {
+            let guild = ctx.cache.guild(guild_id).ok_or("Guild not found")?;
+
+            guild
+                .features
+                .iter()
+                .map(|f| f.to_string())
+                .filter(|f| f != INVITES_DISABLED)
+                .collect::<Vec<String>>()
+        };
+
+        ctx.http
+            .edit_guild(guild_id, &json!({ "features": features }), Some(reason))
+            .await?;
+    }
+
+    // The lockdown is only forgotten once everything was restored, so ending it can be retried
+    sqlx::query!(
+        "DELETE FROM lockdowns WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .execute(pool)
+    .await?;
+
+    info!("Ended lockdown in guild {}", guild_id);
+
+    Ok(true)
+}
@@ -1,22 +1,35 @@
 use std::collections::HashSet;
 
 use log::{error, info};
-use poise::serenity_prelude::{FullEvent, RoleAction, UserId};
-use serenity::model::guild::audit_log::{Action, ChannelAction};
+use poise::serenity_prelude::{FullEvent, RoleAction, RoleId, UserId};
+use serenity::model::guild::audit_log::{Action, ChannelAction, MemberAction};
 use sqlx::postgres::PgPoolOptions;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+mod audit;
 mod autocompletes;
+mod chart;
 mod cmds;
 mod config;
+mod guild_config;
 mod handler;
+mod health;
 mod help;
 mod core;
+mod lockdown;
+mod metrics;
+mod notify;
 mod owner;
+mod presets;
+mod safeguards;
 mod server;
+mod setup;
+mod spam;
 mod stats;
 mod utils;
 mod tasks;
+mod webhooks;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, Data, Error>;
@@ -24,6 +37,13 @@ type Context<'a> = poise::Context<'a, Data, Error>;
 // User data, which is stored and accessible in all command invocations
 pub struct Data {
     pool: sqlx::PgPool,
+    spam_tracker: spam::SpamTracker,
+    spam_configs: spam::SpamConfigCache,
+    notify_ratelimit: notify::NotifyRatelimit,
+    metrics: Arc<metrics::Metrics>,
+    /// Whether the server and task manager have been started, Ready fires for every shard
+    /// and again whenever a shard has to identify anew
+    background_started: AtomicBool,
 }
 
 #[poise::command(prefix_command)]
@@ -90,11 +110,16 @@ async fn event_listener<'a>(
 
             info!("{} is ready!", data_about_bot.user.name);
 
+            if user_data.background_started.swap(true, Ordering::SeqCst) {
+                return Ok(());
+            }
+
             let cache_http_server = botox::cache::CacheHttpImpl::from_ctx(ctx.serenity_context);
 
             tokio::task::spawn(server::setup_server(
                 user_data.pool.clone(),
                 cache_http_server,
+                user_data.metrics.clone(),
             ));
 
             tokio::task::spawn(botox::taskman::start_all_tasks(
@@ -110,6 +135,12 @@ async fn event_listener<'a>(
 
             info!("Audit log created: {:?}. Guild: {}", entry, guild_id);
 
+            user_data
+                .metrics
+                .audit_events
+                .inc(&format!("{:?}", entry.action));
+            user_data.metrics.audit_event_rate.record();
+
             let res = match entry.action {
                 Action::Channel(ch) => {
                     let ch_id = entry.target_id.ok_or("No channel ID found")?;
@@ -177,6 +208,37 @@ async fn event_listener<'a>(
                         RoleAction::Update => {
                             info!("Role updated: {}", r_id);
 
+                            let cache = &ctx.serenity_context.cache;
+                            let bot_id = cache.current_user().id;
+                            let changes = entry.changes.iter().flatten();
+
+                            // Only updates that stop Skynet from enforcing limits are tampering
+                            let tampering = if entry.user_id == bot_id {
+                                None
+                            } else if utils::is_bot_role(cache, *guild_id, RoleId::new(r_id.get()))
+                            {
+                                utils::weakens_bot_role(changes)
+                                    .then(|| format!("weakened the Skynet role <@&{}>", r_id))
+                            } else {
+                                utils::raises_above_bot(cache, *guild_id, changes)
+                                    .then(|| format!("moved <@&{}> above the Skynet role", r_id))
+                            };
+
+                            if let Some(description) = tampering {
+                                if let Err(e) = handler::handle_bot_tamper(
+                                    *guild_id,
+                                    entry.user_id,
+                                    &user_data.pool,
+                                    ctx.serenity_context,
+                                    description,
+                                    r_id.to_string(),
+                                )
+                                .await
+                                {
+                                    error!("Error while handling tampering: {}", e);
+                                }
+                            }
+
                             handler::handle_mod_action(
                                 *guild_id,
                                 entry.user_id,
@@ -203,6 +265,31 @@ async fn event_listener<'a>(
                         _ => Ok(()),
                     }
                 }
+                Action::Member(MemberAction::RoleUpdate) => {
+                    let m_id = entry.target_id.ok_or("No member ID found")?;
+
+                    let bot_id = ctx.serenity_context.cache.current_user().id;
+
+                    // Granting Skynet roles is not tampering, only removing them is
+                    if m_id.get() == bot_id.get()
+                        && entry.user_id != bot_id
+                        && utils::removes_roles(entry.changes.iter().flatten())
+                    {
+                        info!("Skynet roles removed by: {}", entry.user_id);
+
+                        handler::handle_bot_tamper(
+                            *guild_id,
+                            entry.user_id,
+                            &user_data.pool,
+                            ctx.serenity_context,
+                            "removed roles from Skynet".to_string(),
+                            m_id.to_string(),
+                        )
+                        .await
+                    } else {
+                        Ok(())
+                    }
+                }
                 _ => Ok(()),
             };
 
@@ -211,6 +298,68 @@ async fn event_listener<'a>(
                 return Err(res);
             }
         }
+        FullEvent::GuildMemberAddition {
+            new_member,
+        } => {
+            let user_data = ctx.serenity_context.data::<Data>();
+
+            info!("Member joined: {}. Guild: {}", new_member.user.id, new_member.guild_id);
+
+            // A failing join rule should not stop raid detection
+            if let Err(res) =
+                handler::handle_join_rules(new_member, &user_data.pool, ctx.serenity_context).await
+            {
+                error!("Error while handling join rules: {}", res);
+            }
+
+            if let Err(res) = handler::handle_member_join(
+                new_member.guild_id,
+                new_member.user.id,
+                &user_data.pool,
+                ctx.serenity_context,
+            )
+            .await
+            {
+                error!("Error while handling member join: {}", res);
+                return Err(res);
+            }
+        }
+        FullEvent::GuildDelete {
+            incomplete,
+            full,
+        } => {
+            // Unavailable guilds are outages, not removals
+            if !incomplete.unavailable {
+                if let Some(guild) = full {
+                    let user_data = ctx.serenity_context.data::<Data>();
+
+                    if let Err(res) =
+                        handler::handle_bot_removal(guild, &user_data.pool, ctx.serenity_context)
+                            .await
+                    {
+                        error!("Error while handling guild removal: {}", res);
+                    }
+                }
+            }
+        }
+        FullEvent::Message {
+            new_message,
+        } => {
+            let user_data = ctx.serenity_context.data::<Data>();
+
+            if let Err(res) = handler::handle_message(
+                new_message,
+                &user_data.pool,
+                ctx.serenity_context,
+                &user_data.spam_tracker,
+                &user_data.spam_configs,
+            )
+            .await
+            {
+                error!("Error while handling message: {}", res);
+                return Err(res);
+            }
+        }
         _ => {}
     }
 
@@ -234,15 +383,24 @@ async fn main() {
 
     let client_builder = serenity::all::ClientBuilder::new_with_http(
         Arc::new(http),
-        serenity::all::GatewayIntents::non_privileged(),
+        serenity::all::GatewayIntents::non_privileged()
+            | serenity::all::GatewayIntents::GUILD_MEMBERS
+            | serenity::all::GatewayIntents::MESSAGE_CONTENT,
     );
 
+    let metrics = Arc::new(metrics::Metrics::default());
+
     let data = Data {
         pool: PgPoolOptions::new()
         .max_connections(MAX_CONNECTIONS)
         .connect(&config::CONFIG.database_url)
         .await
         .expect("Could not initialize connection"),
+        spam_tracker: spam::SpamTracker::default(),
+        spam_configs: spam::SpamConfigCache::default(),
+        notify_ratelimit: notify::NotifyRatelimit::default(),
+        metrics: metrics.clone(),
+        background_started: AtomicBool::new(false),
     };
 
     // Convert owners to a HashSet
@@ -265,11 +423,23 @@ async fn main() {
                 help::help(),
                 help::simplehelp(),
                 stats::stats(),
+                stats::guildstats(),
+                health::health(),
                 cmds::ping(),
                 cmds::perms(),
-                cmds::setup(),
+                setup::setup(),
                 cmds::limits(),
+                cmds::settings(),
+                cmds::raid(),
+                cmds::joinrules(),
+                cmds::lockdown(),
+                cmds::spam(),
+                cmds::webhooks(),
+                cmds::whitelist(),
                 cmds::actions(),
+                cmds::user(),
+                guild_config::config(),
+                audit::audit(),
                 owner::guild(),
             ],
             command_check: Some(|ctx| {
@@ -279,13 +449,28 @@ async fn main() {
                         return Ok(true);
                     }
 
-                    crate::utils::is_guild_admin(
-                        &ctx,
-                        &ctx.data().pool,
-                        ctx.guild_id().ok_or("Could not get guild id")?,
-                        ctx.author().id.to_string(),
-                    )
-                    .await?;
+                    // Some commands only need a capability instead of full admin
+                    match crate::core::Capability::required_for(&ctx.command().qualified_name) {
+                        Some(capability) => {
+                            crate::utils::has_capability(
+                                &ctx,
+                                &ctx.data().pool,
+                                ctx.guild_id().ok_or("Could not get guild id")?,
+                                ctx.author().id,
+                                capability,
+                            )
+                            .await?;
+                        }
+                        None => {
+                            crate::utils::is_guild_admin(
+                                &ctx,
+                                &ctx.data().pool,
+                                ctx.guild_id().ok_or("Could not get guild id")?,
+                                ctx.author().id.to_string(),
+                            )
+                            .await?;
+                        }
+                    }
 
                     // Look for guild
                     if let Some(guild_id) = ctx.guild_id() {
@@ -348,6 +533,11 @@ async fn main() {
         .await
         .expect("Error creating client");
 
+    tokio::task::spawn(metrics::sample_shards(
+        metrics,
+        client.shard_manager.clone(),
+    ));
+
     if let Err(why) = client.start().await {
         error!("Client error: {:?}", why);
     }
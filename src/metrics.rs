@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serenity::all::{ConnectionStage, ShardManager};
+
+/// How many seconds back event rates are averaged over
+const RATE_WINDOW_SECS: usize = 300;
+
+/// How often the state of the shards is sampled
+const SHARD_SAMPLE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Upper bounds of the latency histogram buckets, in seconds
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// A count that only goes up, split by a label
+#[derive(Default)]
+pub struct Counter {
+    values: Mutex<HashMap<String, u64>>,
+}
+
+impl Counter {
+    pub fn inc(&self, label: &str) {
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+
+        *values.entry(label.to_string()).or_default() += 1;
+    }
+
+    /// The count summed over all labels
+    pub fn total(&self) -> u64 {
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+
+        values.values().sum()
+    }
+
+    /// The count of every label, sorted by label
+    pub fn snapshot(&self) -> Vec<(String, u64)> {
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut snapshot = values
+            .iter()
+            .map(|(k, v)| (k.clone(), *v))
+            .collect::<Vec<_>>();
+
+        snapshot.sort();
+        snapshot
+    }
+}
+
+/// Observed durations of a single label
+#[derive(Clone, Default)]
+pub struct HistogramValues {
+    /// Count of observations per bucket of LATENCY_BUCKETS, not cumulative
+    pub buckets: Vec<u64>,
+    /// Sum of all observations, in seconds
+    pub sum: f64,
+    pub count: u64,
+}
+
+/// Observed durations, split by a label
+#[derive(Default)]
+pub struct Histogram {
+    values: Mutex<HashMap<String, HistogramValues>>,
+}
+
+impl Histogram {
+    pub fn observe(&self, label: &str, duration: Duration) {
+        let secs = duration.as_secs_f64();
+
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+
+        let value = values
+            .entry(label.to_string())
+            .or_insert_with(|| HistogramValues {
+                buckets: vec![0; LATENCY_BUCKETS.len()],
+                ..Default::default()
+            });
+
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|b| secs <= *b) {
+            value.buckets[i] += 1;
+        }
+
+        value.sum += secs;
+        value.count += 1;
+    }
+
+    /// The average duration over all labels, if anything has been observed
+    pub fn average(&self) -> Option<Duration> {
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+
+        let (sum, count) = values
+            .values()
+            .fold((0.0, 0), |(sum, count), v| (sum + v.sum, count + v.count));
+
+        (count > 0).then(|| Duration::from_secs_f64(sum / count as f64))
+    }
+
+    /// The values of every label, sorted by label
+    pub fn snapshot(&self) -> Vec<(String, HistogramValues)> {
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut snapshot = values
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<Vec<_>>();
+
+        snapshot.sort_by(|a, b| a.0.cmp(&b.0));
+        snapshot
+    }
+}
+
+/// Tracks how often something happened recently
+///
+/// Events are counted per second in a ring of buckets, so memory stays the same no matter
+/// how many events there are
+pub struct Rate {
+    started_at: Instant,
+    /// (second since started_at, events in that second), indexed by second modulo the window
+    buckets: Mutex<[(u64, u64); RATE_WINDOW_SECS]>,
+}
+
+impl Default for Rate {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            buckets: Mutex::new([(0, 0); RATE_WINDOW_SECS]),
+        }
+    }
+}
+
+impl Rate {
+    pub fn record(&self) {
+        let second = self.started_at.elapsed().as_secs();
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        let bucket = &mut buckets[second as usize % RATE_WINDOW_SECS];
+
+        // The bucket still holds the count of a second that left the window
+        if bucket.0 != second {
+            *bucket = (second, 0);
+        }
+
+        bucket.1 += 1;
+    }
+
+    /// Events per minute, averaged over the rate window
+    pub fn per_minute(&self) -> f64 {
+        let now = self.started_at.elapsed().as_secs();
+
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        let recent = buckets
+            .iter()
+            .filter(|(second, _)| now.saturating_sub(*second) < RATE_WINDOW_SECS as u64)
+            .map(|(_, count)| count)
+            .sum::<u64>();
+
+        // Shortly after startup only the time since then has been observed
+        let window = now.clamp(1, RATE_WINDOW_SECS as u64);
+
+        recent as f64 / (window as f64 / 60.0)
+    }
+}
+
+/// Runtime statistics of this Skynet process
+pub struct Metrics {
+    pub started_at: Instant,
+    /// Audit log events received, by audit log action
+    pub audit_events: Counter,
+    pub audit_event_rate: Rate,
+    /// Time taken to record and check mod actions, by limit type
+    pub mod_action_latency: Histogram,
+    /// Times the limits of a guild were checked, by the limit type of the action that triggered it
+    pub limit_evaluations: Counter,
+    /// Limits hit, by limit type
+    pub limit_hits: Counter,
+    /// Punishments of hit limits, by outcome
+    pub punishments: Counter,
+    /// Time taken by the queries on the action handling path, by query
+    pub db_query_latency: Histogram,
+    /// Heartbeat latency of the gateway, by shard
+    pub gateway_latency: Histogram,
+    /// Latest sampled state of every shard, by shard id
+    pub shards: Mutex<HashMap<String, ShardStatus>>,
+    /// Background task runs, by task
+    pub task_runs: Counter,
+    /// When each background task last finished, by task
+    pub task_last_run: Mutex<HashMap<String, Instant>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            audit_events: Counter::default(),
+            audit_event_rate: Rate::default(),
+            mod_action_latency: Histogram::default(),
+            limit_evaluations: Counter::default(),
+            limit_hits: Counter::default(),
+            punishments: Counter::default(),
+            db_query_latency: Histogram::default(),
+            gateway_latency: Histogram::default(),
+            shards: Mutex::default(),
+            task_runs: Counter::default(),
+            task_last_run: Mutex::default(),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn task_ran(&self, name: &str) {
+        self.task_runs.inc(name);
+
+        self.task_last_run
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.to_string(), Instant::now());
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        render_gauge(
+            &mut out,
+            "skynet_uptime_seconds",
+            "Seconds since Skynet started",
+            self.uptime().as_secs_f64(),
+        );
+
+        let counters = [
+            (
+                "skynet_audit_events_total",
+                "Audit log events received",
+                "action",
+                &self.audit_events,
+            ),
+            (
+                "skynet_limit_evaluations_total",
+                "Times the limits of a guild were checked",
+                "limit_type",
+                &self.limit_evaluations,
+            ),
+            (
+                "skynet_limit_hits_total",
+                "Limits hit",
+                "limit_type",
+                &self.limit_hits,
+            ),
+            (
+                "skynet_punishments_total",
+                "Punishments of hit limits",
+                "outcome",
+                &self.punishments,
+            ),
+            (
+                "skynet_task_runs_total",
+                "Background task runs",
+                "task",
+                &self.task_runs,
+            ),
+        ];
+
+        for (name, help, label, counter) in counters {
+            render_counter(&mut out, name, help, label, counter);
+        }
+
+        let histograms = [
+            (
+                "skynet_mod_action_duration_seconds",
+                "Time taken to record and check mod actions",
+                "limit_type",
+                &self.mod_action_latency,
+            ),
+            (
+                "skynet_db_query_duration_seconds",
+                "Time taken by the queries on the action handling path",
+                "query",
+                &self.db_query_latency,
+            ),
+            (
+                "skynet_gateway_latency_seconds",
+                "Heartbeat latency of the gateway",
+                "shard",
+                &self.gateway_latency,
+            ),
+        ];
+
+        for (name, help, label, histogram) in histograms {
+            render_histogram(&mut out, name, help, label, histogram);
+        }
+
+        out
+    }
+}
+
+/// Escapes a label value for the Prometheus text format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn render_gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn render_counter(out: &mut String, name: &str, help: &str, label: &str, counter: &Counter) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+
+    for (value, count) in counter.snapshot() {
+        let _ = writeln!(
+            out,
+            "{}{{{}=\"{}\"}} {}",
+            name,
+            label,
+            escape_label(&value),
+            count
+        );
+    }
+}
+
+fn render_histogram(out: &mut String, name: &str, help: &str, label: &str, histogram: &Histogram) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+
+    for (value, values) in histogram.snapshot() {
+        let value = escape_label(&value);
+
+        // Prometheus buckets are cumulative
+        let mut cumulative = 0;
+
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(values.buckets.iter()) {
+            cumulative += count;
+
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}=\"{}\",le=\"{}\"}} {}",
+                name, label, value, bound, cumulative
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}=\"{}\",le=\"+Inf\"}} {}",
+            name, label, value, values.count
+        );
+        let _ = writeln!(
+            out,
+            "{}_sum{{{}=\"{}\"}} {}",
+            name, label, value, values.sum
+        );
+        let _ = writeln!(
+            out,
+            "{}_count{{{}=\"{}\"}} {}",
+            name, label, value, values.count
+        );
+    }
+}
+
+/// Latest sampled state of a shard
+#[derive(Clone, Serialize)]
+pub struct ShardStatus {
+    pub stage: String,
+    pub connected: bool,
+    pub latency_ms: Option<u128>,
+}
+
+/// Periodically records the state and latency of every shard
+pub async fn sample_shards(metrics: Arc<Metrics>, shard_manager: Arc<ShardManager>) {
+    let mut interval = tokio::time::interval(SHARD_SAMPLE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let runners = shard_manager.runners.lock().await;
+
+        let mut shards = HashMap::new();
+
+        for (shard_id, info) in runners.iter() {
+            let connected = matches!(info.stage, ConnectionStage::Connected);
+
+            if connected {
+                if let Some(latency) = info.latency {
+                    metrics
+                        .gateway_latency
+                        .observe(&shard_id.to_string(), latency);
+                }
+            }
+
+            shards.insert(
+                shard_id.to_string(),
+                ShardStatus {
+                    stage: info.stage.to_string(),
+                    connected,
+                    latency_ms: info.latency.map(|l| l.as_millis()),
+                },
+            );
+        }
+
+        *metrics.shards.lock().unwrap_or_else(|e| e.into_inner()) = shards;
+    }
+}
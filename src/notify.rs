@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use log::{error, info};
+use poise::serenity_prelude::{CreateEmbed, GuildId, Mentionable, RoleId, UserId};
+use sqlx::PgPool;
+
+use crate::{
+    core::{Limit, Severity},
+    Error,
+};
+
+/// Most notifications sent per guild within [`RATELIMIT_WINDOW`], so a nuke does not
+/// flood admins with hundreds of DMs
+const RATELIMIT_NOTIFICATIONS: usize = 5;
+const RATELIMIT_WINDOW: Duration = Duration::from_secs(600);
+
+#[derive(Default)]
+pub struct NotifyRatelimit {
+    sent: Mutex<HashMap<GuildId, Vec<Instant>>>,
+}
+
+impl NotifyRatelimit {
+    /// Returns true if a notification may be sent for the guild, counting it if so
+    fn try_acquire(&self, guild_id: GuildId) -> bool {
+        let now = Instant::now();
+
+        let mut sent = self.sent.lock().unwrap_or_else(|e| e.into_inner());
+
+        let recent = sent.entry(guild_id).or_default();
+
+        recent.retain(|s| now.duration_since(*s) < RATELIMIT_WINDOW);
+
+        if recent.len() >= RATELIMIT_NOTIFICATIONS {
+            return false;
+        }
+
+        recent.push(now);
+
+        true
+    }
+}
+
+/// Tells the guild owner and admins that a limit was hit, if the guild wants DMs for it
+pub async fn notify_hit(
+    ctx: &serenity::all::Context,
+    pool: &PgPool,
+    guild_id: GuildId,
+    user_id: UserId,
+    limit: &Limit,
+    failure: Option<String>,
+) {
+    // A failed punishment leaves the guild unprotected
+    let severity = if failure.is_some() {
+        Severity::Critical
+    } else {
+        limit.limit_action.severity()
+    };
+
+    let guild_name = ctx
+        .cache
+        .guild(guild_id)
+        .map(|g| g.name.to_string())
+        .unwrap_or_else(|| guild_id.to_string());
+
+    let embed = CreateEmbed::default()
+        .title(format!("[{}] Limit Hit in {}", severity.to_cond(), guild_name))
+        .description(format!(
+            "{} ({}) hit the limit **{}** [{}]",
+            user_id.mention(),
+            user_id,
+            limit.limit_name,
+            limit.limit_id
+        ))
+        .field("Action", limit.limit_action.to_cond(), true)
+        .field(
+            "Result",
+            match failure {
+                Some(e) => format!("Failed: {}", e),
+                None => "Succeeded".to_string(),
+            },
+            true,
+        )
+        .color(if severity == Severity::Critical {
+            0xff0000
+        } else {
+            0xffa500
+        });
+
+    if let Err(e) = notify_admins(ctx, pool, guild_id, severity, embed).await {
+        error!("Failed to notify admins of guild {}: {}", guild_id, e);
+    }
+}
+
+/// DMs the guild owner and admins if the severity is at least the one configured by the guild
+pub async fn notify_admins(
+    ctx: &serenity::all::Context,
+    pool: &PgPool,
+    guild_id: GuildId,
+    severity: Severity,
+    embed: CreateEmbed<'_>,
+) -> Result<(), Error> {
+    let rec = sqlx::query!(
+        "SELECT dm_severity FROM guilds WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(dm_severity) = rec.and_then(|r| r.dm_severity) else {
+        // DMs are disabled
+        return Ok(());
+    };
+
+    if severity < dm_severity.parse::<Severity>()? {
+        return Ok(());
+    }
+
+    if !ctx
+        .data::<crate::Data>()
+        .notify_ratelimit
+        .try_acquire(guild_id)
+    {
+        info!("Not notifying admins of guild {}, ratelimited", guild_id);
+        return Ok(());
+    }
+
+    let admins = sqlx::query!(
+        "SELECT user_id FROM guild_admins WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let admin_roles = sqlx::query!(
+        "SELECT role_id FROM guild_admin_roles WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| r.role_id.parse::<RoleId>())
+    .collect::<Result<Vec<_>, _>>()?;
+
+    let mut recipients = {
+        let guild = ctx.cache.guild(guild_id).ok_or("Guild not found")?;
+
+        // Members with an admin role are admins too
+        let mut recipients = vec![guild.owner_id];
+
+        for member in guild.members.values() {
+            if member.roles.iter().any(|r| admin_roles.contains(r))
+                && !recipients.contains(&member.user.id)
+            {
+                recipients.push(member.user.id);
+            }
+        }
+
+        recipients
+    };
+
+    for admin in admins {
+        let admin = admin.user_id.parse::<UserId>()?;
+
+        if !recipients.contains(&admin) {
+            recipients.push(admin);
+        }
+    }
+
+    for recipient in recipients {
+        if let Err(e) = crate::utils::send_dm(ctx, recipient, embed.clone()).await {
+            error!("Failed to DM {}: {}", recipient, e);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use serde::Serialize;
+use strum_macros::{Display, EnumString, EnumVariantNames};
+
+use crate::core::{Limit, UserLimitActions, UserLimitTypes};
+
+#[derive(poise::ChoiceParameter)]
+pub enum PresetChoices {
+    #[name = "Strict"]
+    Strict,
+    #[name = "Balanced"]
+    Balanced,
+    #[name = "Lenient"]
+    Lenient,
+}
+
+impl PresetChoices {
+    pub fn resolve(self) -> Preset {
+        match self {
+            Self::Strict => Preset::Strict,
+            Self::Balanced => Preset::Balanced,
+            Self::Lenient => Preset::Lenient,
+        }
+    }
+}
+
+/// Built-in bundles of limits for guilds that do not want to tune every limit themselves
+#[derive(EnumString, Display, PartialEq, EnumVariantNames, Clone, Debug, Serialize)]
+#[strum(serialize_all = "snake_case")]
+pub enum Preset {
+    Strict,
+    Balanced,
+    Lenient,
+}
+
+/// A limit that is part of a preset
+#[derive(Clone, Debug, Serialize)]
+pub struct PresetLimit {
+    pub limit_name: String,
+    pub limit_type: UserLimitTypes,
+    pub limit_per: i32,
+    /// Interval in seconds
+    pub limit_time: i64,
+    pub limit_action: UserLimitActions,
+}
+
+impl PresetLimit {
+    fn from_limit(limit: &Limit) -> Self {
+        Self {
+            limit_name: limit.limit_name.clone(),
+            limit_type: limit.limit_type.clone(),
+            limit_per: limit.limit_per,
+            limit_time: crate::utils::pg_interval_to_secs(&limit.limit_time),
+            limit_action: limit.limit_action.clone(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "over {} in {:?}: ``{}``",
+            self.limit_per,
+            std::time::Duration::from_secs(self.limit_time.try_into().unwrap_or_default()),
+            self.limit_action.to_cond()
+        )
+    }
+}
+
+/// How applying a preset changes the limits of a single limit type
+pub enum PresetChange {
+    /// The guild has no limit of this type covering all targets yet
+    Add(PresetLimit),
+    /// The existing limit covering all targets will be updated
+    Update {
+        limit_id: String,
+        old: PresetLimit,
+        new: PresetLimit,
+    },
+    /// The existing limit covering all targets already matches the preset
+    Unchanged(PresetLimit),
+}
+
+impl PresetChange {
+    /// Returns true if this change raises a threshold or softens a punishment
+    pub fn weakens(&self) -> bool {
+        match self {
+            Self::Update { old, new, .. } => {
+                new.limit_per > old.limit_per
+                    || new.limit_time < old.limit_time
+                    || new.limit_action.severity() < old.limit_action.severity()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Preset {
+    pub fn to_cond(&self) -> String {
+        match &self {
+            Self::Strict => "Strict".to_string(),
+            Self::Balanced => "Balanced".to_string(),
+            Self::Lenient => "Lenient".to_string(),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match &self {
+            Self::Strict => {
+                "Low thresholds over long intervals, for servers that rarely moderate".to_string()
+            }
+            Self::Balanced => "Sensible thresholds for most servers".to_string(),
+            Self::Lenient => "High thresholds, for large servers with busy moderators".to_string(),
+        }
+    }
+
+    /// The limits making up this preset, one for every limit type
+    pub fn limits(&self) -> Vec<PresetLimit> {
+        use UserLimitActions::*;
+        use UserLimitTypes::*;
+
+        // (limit type, balanced limit_per, balanced interval, action)
+        let balanced = [
+            (RoleAdd, 5, 600, RemoveAllRoles),
+            (RoleUpdate, 10, 600, RemoveAllRoles),
+            (RoleRemove, 3, 600, RemoveAllRoles),
+            (ChannelAdd, 5, 600, RemoveAllRoles),
+            (ChannelUpdate, 10, 600, RemoveAllRoles),
+            (ChannelRemove, 3, 600, RemoveAllRoles),
+            (Kick, 5, 600, RemoveAllRoles),
+            (Ban, 3, 600, RemoveAllRoles),
+            (Unban, 5, 600, RemoveAllRoles),
+            (MessageSpam, 3, 60, KickUser),
+            (MassMention, 20, 60, KickUser),
+            (BotTamper, 1, 60, BanUser),
+            (ConfigDelete, 5, 600, RemoveAllRoles),
+            (ThreatScore, 20, 600, RemoveAllRoles),
+        ];
+
+        balanced
+            .into_iter()
+            .map(|(limit_type, limit_per, limit_time, limit_action)| {
+                // Any tampering with Skynet is always an immediate hit
+                let (limit_per, limit_time) = match (self, &limit_type) {
+                    (_, BotTamper) => (limit_per, limit_time),
+                    (Self::Strict, _) => ((limit_per / 2).max(1), limit_time * 2),
+                    (Self::Balanced, _) => (limit_per, limit_time),
+                    (Self::Lenient, _) => (limit_per * 2, limit_time),
+                };
+
+                PresetLimit {
+                    limit_name: format!("{} {}", self.to_cond(), limit_type.to_cond()),
+                    limit_type,
+                    limit_per,
+                    limit_time,
+                    limit_action,
+                }
+            })
+            .collect()
+    }
+
+    /// Compares the preset against a guilds limits. Limits scoped to specific targets are left alone
+    pub fn diff(&self, existing: &[Limit]) -> Vec<PresetChange> {
+        self.limits()
+            .into_iter()
+            .map(|new| {
+                let old = existing
+                    .iter()
+                    .find(|l| l.limit_type == new.limit_type && l.limit_targets.is_empty());
+
+                match old {
+                    None => PresetChange::Add(new),
+                    Some(old) => {
+                        let limit_id = old.limit_id.clone();
+                        let old = PresetLimit::from_limit(old);
+
+                        if old.limit_per == new.limit_per
+                            && old.limit_time == new.limit_time
+                            && old.limit_action == new.limit_action
+                        {
+                            PresetChange::Unchanged(old)
+                        } else {
+                            PresetChange::Update { limit_id, old, new }
+                        }
+                    }
+                }
+            })
+            .collect()
+    }
+}
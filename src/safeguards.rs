@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use log::warn;
+use poise::serenity_prelude::{
+    ButtonStyle, ComponentInteractionCollector, CreateActionRow, CreateButton, CreateEmbed,
+    CreateInteractionResponse, Mentionable, UserId,
+};
+use poise::CreateReply;
+use sqlx::types::chrono::Utc;
+
+use crate::core::{SafeguardMode, UserLimitTypes};
+
+type Error = crate::Error;
+type Context<'a> = crate::Context<'a>;
+
+/// How long a change that has waited out its cooldown can be confirmed for before it has to wait again
+const COOLDOWN_CONFIRM_WINDOW_SECS: i64 = 86400;
+
+/// How long the owner has to approve or deny a change
+const OWNER_CONFIRM_TIMEOUT_SECS: u64 = 600;
+
+/// Checks whether a change that weakens protection may be made right now
+///
+/// Returns false if the change has been held back, in which case the user has already been told why
+pub async fn allow_weakening(ctx: Context<'_>, change: &str) -> Result<bool, Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+    let owner_id = ctx.guild().ok_or("Could not get guild id")?.owner_id;
+
+    // The owner can always change their own server
+    if ctx.author().id == owner_id {
+        return Ok(true);
+    }
+
+    let rec = sqlx::query!(
+        "SELECT safeguard_mode, safeguard_cooldown FROM guilds WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .fetch_one(&ctx.data().pool)
+    .await?;
+
+    let Some(mode) = rec.safeguard_mode else {
+        return Ok(true);
+    };
+
+    let mode = mode.parse::<SafeguardMode>()?;
+
+    match mode {
+        SafeguardMode::Cooldown => {
+            cooldown(
+                ctx,
+                owner_id,
+                change,
+                crate::utils::pg_interval_to_secs(&rec.safeguard_cooldown),
+            )
+            .await
+        }
+        SafeguardMode::OwnerConfirmation => owner_confirmation(ctx, owner_id, change).await,
+    }
+}
+
+/// The change is applied once the same command is run again after the cooldown
+async fn cooldown(
+    ctx: Context<'_>,
+    owner_id: UserId,
+    change: &str,
+    cooldown_secs: i64,
+) -> Result<bool, Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    let req = sqlx::query!(
+        "
+            SELECT created_at FROM safeguard_requests
+            WHERE guild_id = $1
+            AND user_id = $2
+            AND change = $3
+        ",
+        guild_id.to_string(),
+        ctx.author().id.to_string(),
+        change
+    )
+    .fetch_optional(&ctx.data().pool)
+    .await?;
+
+    if let Some(req) = req {
+        let elapsed = (Utc::now() - req.created_at).num_seconds();
+
+        if elapsed < cooldown_secs {
+            ctx.say(format!(
+                "This change is still cooling down, run the same command again <t:{}:R> to apply it",
+                req.created_at.timestamp() + cooldown_secs
+            ))
+            .await?;
+            return Ok(false);
+        }
+
+        if elapsed < cooldown_secs + COOLDOWN_CONFIRM_WINDOW_SECS {
+            sqlx::query!(
+                "DELETE FROM safeguard_requests WHERE guild_id = $1 AND user_id = $2 AND change = $3",
+                guild_id.to_string(),
+                ctx.author().id.to_string(),
+                change
+            )
+            .execute(&ctx.data().pool)
+            .await?;
+
+            return Ok(true);
+        }
+    }
+
+    // New or expired request, start the cooldown (again)
+    sqlx::query!(
+        "
+            INSERT INTO safeguard_requests (guild_id, user_id, change)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id, user_id, change) DO UPDATE SET created_at = NOW()
+        ",
+        guild_id.to_string(),
+        ctx.author().id.to_string(),
+        change
+    )
+    .execute(&ctx.data().pool)
+    .await?;
+
+    alert_owner(ctx, owner_id, change, SafeguardMode::Cooldown).await;
+
+    ctx.say(format!(
+        "This change weakens Skynet's protection and has to wait out a cooldown. Run the same command again <t:{}:R> to apply it",
+        Utc::now().timestamp() + cooldown_secs
+    ))
+    .await?;
+
+    Ok(false)
+}
+
+/// The change is applied once the owner approves it using a button
+async fn owner_confirmation(
+    ctx: Context<'_>,
+    owner_id: UserId,
+    change: &str,
+) -> Result<bool, Error> {
+    let components = vec![CreateActionRow::Buttons(vec![
+        CreateButton::new("safeguard_approve")
+            .label("Approve")
+            .style(ButtonStyle::Success),
+        CreateButton::new("safeguard_deny")
+            .label("Deny")
+            .style(ButtonStyle::Danger),
+    ])];
+
+    alert_owner(ctx, owner_id, change, SafeguardMode::OwnerConfirmation).await;
+
+    let handle = ctx
+        .send(
+            CreateReply::default()
+                .content(format!(
+                    "{}, {} wants to ``{}``, which weakens Skynet's protection. Only the owner can approve this change",
+                    owner_id.mention(),
+                    ctx.author().mention(),
+                    change
+                ))
+                .components(components),
+        )
+        .await?;
+
+    let msg = handle.message().await?;
+
+    let interaction = ComponentInteractionCollector::new(ctx.serenity_context())
+        .author_id(owner_id)
+        .message_id(msg.id)
+        .timeout(Duration::from_secs(OWNER_CONFIRM_TIMEOUT_SECS))
+        .await;
+
+    let (approved, content) = match interaction {
+        Some(interaction) => {
+            interaction
+                .create_response(ctx.http(), CreateInteractionResponse::Acknowledge)
+                .await?;
+
+            if interaction.data.custom_id.as_str() == "safeguard_approve" {
+                (true, "The owner approved this change")
+            } else {
+                (false, "The owner denied this change")
+            }
+        }
+        None => (
+            false,
+            "The owner did not respond in time, the change was not made",
+        ),
+    };
+
+    handle
+        .edit(ctx, CreateReply::default().content(content).components(vec![]))
+        .await?;
+
+    Ok(approved)
+}
+
+/// Tells the owner and the log channel that a weakening change has been requested
+async fn alert_owner(ctx: Context<'_>, owner_id: UserId, change: &str, mode: SafeguardMode) {
+    let Some(guild_id) = ctx.guild_id() else {
+        return;
+    };
+
+    let embed = CreateEmbed::new()
+        .title("Protection Change Requested")
+        .description(format!(
+            "{} wants to ``{}``, which weakens Skynet's protection",
+            ctx.author().mention(),
+            change
+        ))
+        .field("Safeguard", mode.to_cond(), true)
+        .color(0xff0000);
+
+    if let Err(e) = crate::utils::send_log(
+        ctx.serenity_context(),
+        &ctx.data().pool,
+        guild_id,
+        embed.clone(),
+    )
+    .await
+    {
+        warn!("Could not post safeguard request to log channel: {}", e);
+    }
+
+    if let Err(e) = crate::utils::send_dm(ctx.serenity_context(), owner_id, embed).await {
+        warn!("Could not DM owner about safeguard request: {}", e);
+    }
+}
+
+/// Records the deletion of a piece of Skynet config as a ConfigDelete action of the user running the command
+pub async fn record_deletion(ctx: Context<'_>, target: String) -> Result<(), Error> {
+    crate::handler::handle_mod_action(
+        ctx.guild_id().ok_or("Could not get guild id")?,
+        ctx.author().id,
+        &ctx.data().pool,
+        ctx.serenity_context(),
+        UserLimitTypes::ConfigDelete,
+        target,
+    )
+    .await
+}
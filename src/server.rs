@@ -1,31 +1,47 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::http::header;
 use axum::{
     extract::{Path, Query, State},
-    http::{HeaderName, StatusCode},
+    http::{HeaderMap, HeaderName, StatusCode},
     response::{IntoResponse, Redirect, Response},
     routing::get,
-    Router,
+    Json, Router,
 };
 use log::info;
 use poise::serenity_prelude::{GuildId, UserId};
 use serde::Deserialize;
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
 use tower_http::cors::{Any, CorsLayer};
 
 use botox::cache::CacheHttpImpl;
 
+use crate::metrics::Metrics;
+
 pub struct AppState {
     pub cache_http: CacheHttpImpl,
     pub pool: PgPool,
+    pub metrics: Arc<Metrics>,
 }
 
-pub async fn setup_server(pool: PgPool, cache_http: CacheHttpImpl) {
-    let shared_state = Arc::new(AppState { pool, cache_http });
+/// Extra time a background task may take before it counts as overdue, covering the time
+/// until the task manager is started once Skynet is connected
+const TASK_GRACE: Duration = Duration::from_secs(300);
+
+pub async fn setup_server(pool: PgPool, cache_http: CacheHttpImpl, metrics: Arc<Metrics>) {
+    let shared_state = Arc::new(AppState {
+        pool,
+        cache_http,
+        metrics,
+    });
 
     let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(get_metrics))
         .route("/:gid", get(create_login))
         .route("/confirm-login", get(confirm_login))
         .with_state(shared_state)
@@ -60,6 +76,149 @@ impl IntoResponse for ServerError {
     }
 }
 
+/// Checks that the database can be queried
+async fn check_database(pool: &PgPool) -> (bool, serde_json::Value) {
+    let start = std::time::Instant::now();
+
+    let res = sqlx::query!("SELECT 1 AS \"one!\"").fetch_one(pool).await;
+
+    let ok = res.is_ok();
+
+    (
+        ok,
+        json!({
+            "ok": ok,
+            "error": res.err().map(|e| e.to_string()),
+            "latency_ms": start.elapsed().as_millis(),
+            "pool_size": pool.size(),
+            "pool_idle": pool.num_idle(),
+        }),
+    )
+}
+
+/// Checks that every shard is connected to the gateway, as of the last sample
+fn check_gateway(metrics: &Metrics) -> (bool, serde_json::Value) {
+    let shards = metrics
+        .shards
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+
+    // No shards have been sampled yet while starting up
+    let ok = !shards.is_empty() && shards.values().all(|s| s.connected);
+
+    (ok, json!({ "ok": ok, "shards": shards }))
+}
+
+/// Checks that the background tasks are still being run, a task is overdue once it missed
+/// two runs
+fn check_tasks(metrics: &Metrics) -> (bool, serde_json::Value) {
+    let last_runs = metrics
+        .task_last_run
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+
+    let mut ok = true;
+    let mut tasks = serde_json::Map::new();
+
+    for task in crate::tasks::tasks().into_iter().filter(|t| t.enabled) {
+        // Tasks that have not finished yet are timed from startup
+        let since = last_runs
+            .get(task.name)
+            .copied()
+            .unwrap_or(metrics.started_at)
+            .elapsed();
+
+        let task_ok = since <= task.duration * 2 + TASK_GRACE;
+        ok &= task_ok;
+
+        tasks.insert(
+            task.name.to_string(),
+            json!({ "ok": task_ok, "secs_since_run": since.as_secs() }),
+        );
+    }
+
+    (ok, json!({ "ok": ok, "tasks": tasks }))
+}
+
+fn health_response(ok: bool, checks: serde_json::Value) -> Response {
+    let status = if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(json!({
+            "status": if ok { "ok" } else { "error" },
+            "checks": checks,
+        })),
+    )
+        .into_response()
+}
+
+/// Liveness check, fails only if Skynet needs to be restarted
+async fn healthz(State(app_state): State<Arc<AppState>>) -> Response {
+    let (tasks_ok, tasks) = check_tasks(&app_state.metrics);
+
+    health_response(
+        tasks_ok,
+        json!({
+            "uptime_secs": app_state.metrics.uptime().as_secs(),
+            "tasks": tasks,
+        }),
+    )
+}
+
+/// Readiness check, fails while Skynet cannot handle events
+async fn readyz(State(app_state): State<Arc<AppState>>) -> Response {
+    let (db_ok, database) = check_database(&app_state.pool).await;
+    let (gateway_ok, gateway) = check_gateway(&app_state.metrics);
+    let (tasks_ok, tasks) = check_tasks(&app_state.metrics);
+
+    health_response(
+        db_ok && gateway_ok && tasks_ok,
+        json!({
+            "database": database,
+            "gateway": gateway,
+            "tasks": tasks,
+        }),
+    )
+}
+
+/// Checks the bearer token of a metrics scrape against the configured one
+fn metrics_authorized(headers: &HeaderMap, token: &str) -> bool {
+    let Some(given) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+
+    // Comparing hashes keeps the comparison time independent of the token
+    Sha256::digest(given.as_bytes()) == Sha256::digest(token.as_bytes())
+}
+
+/// Prometheus metrics of this Skynet process, requires the metrics_token as a bearer token
+async fn get_metrics(State(app_state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    let Some(ref token) = crate::config::CONFIG.metrics_token else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if !metrics_authorized(&headers, token) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        app_state.metrics.render(),
+    )
+        .into_response()
+}
+
 async fn create_login(Path(gid): Path<UserId>) -> Redirect {
     // Redirect user to the login page
     let url = format!("https://discord.com/api/oauth2/authorize?client_id={}&redirect_uri={}/confirm-login&scope={}&state={}&response_type=code", crate::config::CONFIG.client_id, crate::config::CONFIG.frontend_url, "identify", gid);
@@ -0,0 +1,328 @@
+use poise::serenity_prelude::{
+    ButtonStyle, ChannelId, ChannelType, ComponentInteractionDataKind, CreateActionRow,
+    CreateButton, CreateEmbed, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption,
+    Mentionable, MessageId, RoleId,
+};
+use poise::{CreateReply, ReplyHandle};
+use serde_json::json;
+use strum::VariantNames;
+
+use crate::presets::Preset;
+
+type Error = crate::Error;
+type Context<'a> = crate::Context<'a>;
+
+/// How long the wizard waits for each answer before giving up
+const WIZARD_TIMEOUT_SECS: u64 = 300;
+
+/// Everything chosen in the wizard
+#[derive(Default)]
+struct SetupChoices {
+    preset: Option<Preset>,
+    log_channel: Option<ChannelId>,
+    quarantine_role: Option<RoleId>,
+}
+
+fn skip_button(custom_id: &'static str) -> CreateActionRow<'static> {
+    CreateActionRow::Buttons(vec![CreateButton::new(custom_id)
+        .label("Skip")
+        .style(ButtonStyle::Secondary)])
+}
+
+fn preset_step() -> CreateReply<'static> {
+    let mut options = Preset::VARIANTS
+        .iter()
+        .filter_map(|v| v.parse::<Preset>().ok())
+        .map(|p| {
+            CreateSelectMenuOption::new(p.to_cond(), p.to_string()).description(p.description())
+        })
+        .collect::<Vec<_>>();
+
+    options.push(
+        CreateSelectMenuOption::new("None", "none")
+            .description("Add limits yourself with /limits add"),
+    );
+
+    CreateReply::default()
+        .content("**Step 1/4:** Pick a protection preset. Every preset adds one limit for each kind of action Skynet watches, they can be changed later")
+        .components(vec![CreateActionRow::SelectMenu(CreateSelectMenu::new(
+            "setup_preset",
+            CreateSelectMenuKind::String {
+                options: options.into(),
+            },
+        ))])
+}
+
+fn log_channel_step() -> CreateReply<'static> {
+    CreateReply::default()
+        .content(
+            "**Step 2/4:** Choose a channel for Skynet to post raids, limit hits and other logs to",
+        )
+        .components(vec![
+            CreateActionRow::SelectMenu(CreateSelectMenu::new(
+                "setup_log_channel",
+                CreateSelectMenuKind::Channel {
+                    channel_types: Some(vec![ChannelType::Text].into()),
+                    default_channels: None,
+                },
+            )),
+            skip_button("setup_skip_log_channel"),
+        ])
+}
+
+fn quarantine_role_step() -> CreateReply<'static> {
+    CreateReply::default()
+        .content("**Step 3/4:** Choose a role to give members quarantined by join rules")
+        .components(vec![
+            CreateActionRow::SelectMenu(CreateSelectMenu::new(
+                "setup_quarantine_role",
+                CreateSelectMenuKind::Role {
+                    default_roles: None,
+                },
+            )),
+            skip_button("setup_skip_quarantine_role"),
+        ])
+}
+
+/// Summarizes the choices and checks that Skynet can act on them
+fn review_step(ctx: Context<'_>, choices: &SetupChoices) -> Result<CreateReply<'static>, Error> {
+    let bot_id = ctx.cache().current_user().id;
+    let guild = ctx.guild().ok_or("Could not get guild from cache")?;
+
+    // Preset limits do not exist yet, but the permissions their actions need are always required anyways
+    let health = crate::health::HealthReport::check(&guild, bot_id, &[])?;
+
+    let bot_position = guild
+        .members
+        .get(&bot_id)
+        .ok_or("Could not find Skynet in the guild")?
+        .roles
+        .iter()
+        .filter_map(|r| guild.roles.get(r))
+        .map(|r| r.position)
+        .max()
+        .unwrap_or_default();
+
+    let mut problems = Vec::new();
+
+    if let Some(role_id) = choices.quarantine_role {
+        if let Some(role) = guild.roles.get(&role_id) {
+            if role.position >= bot_position {
+                problems.push(format!(
+                    "{} is not below Skynet's role, so Skynet cannot give it out",
+                    role_id.mention()
+                ));
+            }
+        }
+    }
+
+    let summary = CreateEmbed::new()
+        .title("Setup Summary")
+        .field(
+            "Preset",
+            match choices.preset {
+                Some(ref preset) => {
+                    format!("{} ({} limits)", preset.to_cond(), preset.limits().len())
+                }
+                None => "None".to_string(),
+            },
+            false,
+        )
+        .field(
+            "Log Channel",
+            match choices.log_channel {
+                Some(channel_id) => channel_id.mention().to_string(),
+                None => "None".to_string(),
+            },
+            true,
+        )
+        .field(
+            "Quarantine Role",
+            match choices.quarantine_role {
+                Some(role_id) => role_id.mention().to_string(),
+                None => "None".to_string(),
+            },
+            true,
+        )
+        .color(0x00ff00);
+
+    let mut health_embed = health.to_embed();
+
+    if !problems.is_empty() {
+        health_embed = health_embed
+            .field("Other Problems", problems.join("\n"), false)
+            .color(0xff0000);
+    }
+
+    Ok(CreateReply::default()
+        .content("**Step 4/4:** Review your choices. Problems found below can be fixed after finishing setup, run ``/health`` to check again")
+        .embed(summary)
+        .embed(health_embed)
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new("setup_finish")
+                .label("Finish Setup")
+                .style(ButtonStyle::Success),
+            CreateButton::new("setup_cancel")
+                .label("Cancel")
+                .style(ButtonStyle::Danger),
+        ])]))
+}
+
+/// Ends the wizard, removing all components from the message
+async fn finish(ctx: Context<'_>, handle: &ReplyHandle<'_>, content: &str) -> Result<(), Error> {
+    handle
+        .edit(
+            ctx,
+            CreateReply::default()
+                .content(content.to_string())
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Setup the bot if it is not already setup
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn setup(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    // Check if guild is already setup
+    let count = sqlx::query!(
+        "SELECT COUNT(*) FROM guilds WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .fetch_one(&ctx.data().pool)
+    .await
+    .map_err(|_| "Could not fetch guild status")?;
+
+    if count.count.unwrap_or_default() > 0 {
+        return Err("Guild is already setup".into());
+    }
+
+    let mut choices = SetupChoices::default();
+
+    let handle = ctx.send(preset_step()).await?;
+    let msg_id = handle.message().await?.id;
+
+    // Step 1: preset
+    let Some(interaction) = crate::utils::next_component(ctx, msg_id, WIZARD_TIMEOUT_SECS).await?
+    else {
+        return finish(ctx, &handle, "Setup timed out, run ``/setup`` to try again").await;
+    };
+
+    if let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind {
+        choices.preset = values.first().and_then(|v| v.parse::<Preset>().ok());
+    }
+
+    // Step 2: log channel
+    handle.edit(ctx, log_channel_step()).await?;
+
+    let Some(interaction) = crate::utils::next_component(ctx, msg_id, WIZARD_TIMEOUT_SECS).await?
+    else {
+        return finish(ctx, &handle, "Setup timed out, run ``/setup`` to try again").await;
+    };
+
+    if let ComponentInteractionDataKind::ChannelSelect { values } = &interaction.data.kind {
+        choices.log_channel = values.first().copied();
+    }
+
+    // Step 3: quarantine role
+    handle.edit(ctx, quarantine_role_step()).await?;
+
+    let Some(interaction) = crate::utils::next_component(ctx, msg_id, WIZARD_TIMEOUT_SECS).await?
+    else {
+        return finish(ctx, &handle, "Setup timed out, run ``/setup`` to try again").await;
+    };
+
+    if let ComponentInteractionDataKind::RoleSelect { values } = &interaction.data.kind {
+        choices.quarantine_role = values.first().copied();
+    }
+
+    // Step 4: review
+    handle.edit(ctx, review_step(ctx, &choices)?).await?;
+
+    let Some(interaction) = crate::utils::next_component(ctx, msg_id, WIZARD_TIMEOUT_SECS).await?
+    else {
+        return finish(ctx, &handle, "Setup timed out, run ``/setup`` to try again").await;
+    };
+
+    if interaction.data.custom_id.as_str() != "setup_finish" {
+        return finish(ctx, &handle, "Setup cancelled, nothing was changed").await;
+    }
+
+    let limits = choices
+        .preset
+        .as_ref()
+        .map(|p| p.limits())
+        .unwrap_or_default();
+
+    // Add guild and its limits to db
+    let mut tx = ctx.data().pool.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO guilds (guild_id, log_channel, quarantine_role) VALUES ($1, $2, $3)",
+        guild_id.to_string(),
+        choices.log_channel.map(|c| c.to_string()),
+        choices.quarantine_role.map(|r| r.to_string())
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    for limit in limits.iter() {
+        sqlx::query!(
+            "
+                INSERT INTO limits (
+                    guild_id,
+                    limit_name,
+                    limit_type,
+                    limit_action,
+                    limit_per,
+                    limit_time
+                )
+                VALUES (
+                    $1,
+                    $2,
+                    $3,
+                    $4,
+                    $5,
+                    make_interval(secs => $6)
+                )
+            ",
+            guild_id.to_string(),
+            limit.limit_name,
+            limit.limit_type.to_string(),
+            limit.limit_action.to_string(),
+            limit.limit_per,
+            limit.limit_time as f64
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let log = crate::audit::record(
+        ctx,
+        &mut tx,
+        None,
+        Some(json!({
+            "preset": choices.preset.as_ref().map(|p| p.to_string()),
+            "log_channel": choices.log_channel.map(|c| c.to_string()),
+            "quarantine_role": choices.quarantine_role.map(|r| r.to_string()),
+            "limits": limits,
+        })),
+    )
+    .await?;
+
+    tx.commit().await?;
+    log.post(ctx).await;
+
+    finish(
+        ctx,
+        &handle,
+        &format!(
+            "Setup successfully with {} limits. Use ``/limits view`` to see them and ``/limits add`` to add more",
+            limits.len()
+        ),
+    )
+    .await
+}
@@ -0,0 +1,134 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use poise::serenity_prelude::{ChannelId, GuildId, UserId};
+use sqlx::PgPool;
+
+use crate::{core::SpamConfig, Error};
+
+/// Messages older than this are forgotten regardless of the configured interval
+pub const MAX_TRACKED: Duration = Duration::from_secs(3600);
+
+/// How long the spam detection settings of a guild are cached for
+const CONFIG_TTL: Duration = Duration::from_secs(60);
+
+struct RecentMessage {
+    content: u64,
+    channel_id: ChannelId,
+    sent_at: Instant,
+}
+
+/// How often a message has recently been repeated by its author
+pub struct Repeats {
+    /// Identical messages sent within the interval, including this one
+    pub duplicates: usize,
+    /// Distinct channels the message was sent to within the interval
+    pub channels: usize,
+}
+
+/// Keeps track of recent messages per member to detect repeated and cross channel spam
+///
+/// Only a hash of the message content is kept
+#[derive(Default)]
+pub struct SpamTracker {
+    messages: Mutex<HashMap<(GuildId, UserId), Vec<RecentMessage>>>,
+}
+
+impl SpamTracker {
+    /// Records a message and returns how often it was repeated within the interval
+    pub fn track(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        channel_id: ChannelId,
+        content: &str,
+        interval: Duration,
+    ) -> Repeats {
+        let mut hasher = DefaultHasher::new();
+        content.trim().to_lowercase().hash(&mut hasher);
+        let content = hasher.finish();
+
+        let now = Instant::now();
+        let interval = interval.min(MAX_TRACKED);
+
+        let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+
+        let recent = messages.entry((guild_id, user_id)).or_default();
+
+        recent.retain(|m| now.duration_since(m.sent_at) < interval);
+
+        recent.push(RecentMessage {
+            content,
+            channel_id,
+            sent_at: now,
+        });
+
+        let mut channels = Vec::new();
+        let mut duplicates = 0;
+
+        for m in recent.iter().filter(|m| m.content == content) {
+            duplicates += 1;
+
+            if !channels.contains(&m.channel_id) {
+                channels.push(m.channel_id);
+            }
+        }
+
+        Repeats {
+            duplicates,
+            channels: channels.len(),
+        }
+    }
+
+    /// Forgets all messages that are too old to be tracked
+    pub fn prune(&self) {
+        let now = Instant::now();
+
+        let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+
+        messages.retain(|_, recent| {
+            recent.retain(|m| now.duration_since(m.sent_at) < MAX_TRACKED);
+            !recent.is_empty()
+        });
+    }
+}
+
+/// Caches the spam detection settings of every guild, as they are needed for every message
+#[derive(Default)]
+pub struct SpamConfigCache {
+    configs: Mutex<HashMap<GuildId, (Instant, Option<SpamConfig>)>>,
+}
+
+impl SpamConfigCache {
+    /// Returns the spam detection settings of a guild, if spam detection is enabled
+    pub async fn get(&self, pool: &PgPool, guild_id: GuildId) -> Result<Option<SpamConfig>, Error> {
+        {
+            let configs = self.configs.lock().unwrap_or_else(|e| e.into_inner());
+
+            if let Some((fetched_at, config)) = configs.get(&guild_id) {
+                if fetched_at.elapsed() < CONFIG_TTL {
+                    return Ok(config.clone());
+                }
+            }
+        }
+
+        let config = SpamConfig::from_guild(pool, guild_id).await?;
+
+        let mut configs = self.configs.lock().unwrap_or_else(|e| e.into_inner());
+
+        configs.insert(guild_id, (Instant::now(), config.clone()));
+
+        Ok(config)
+    }
+
+    /// Forgets the settings of a guild, so changes apply immediately
+    pub fn invalidate(&self, guild_id: GuildId) {
+        let mut configs = self.configs.lock().unwrap_or_else(|e| e.into_inner());
+
+        configs.remove(&guild_id);
+    }
+}
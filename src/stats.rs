@@ -1,4 +1,9 @@
-use poise::{serenity_prelude::CreateEmbed, CreateReply};
+use std::time::Duration;
+
+use poise::{
+    serenity_prelude::{CreateAttachment, CreateEmbed, CreateEmbedFooter},
+    CreateReply,
+};
 
 type Error = crate::Error;
 type Context<'a> = crate::Context<'a>;
@@ -12,8 +17,38 @@ pub const BUILD_CPU: &str = env!("VERGEN_SYSINFO_CPU_BRAND");
 pub const CARGO_PROFILE: &str = env!("VERGEN_CARGO_PROFILE");
 pub const RUSTC_VERSION: &str = env!("VERGEN_RUSTC_SEMVER");
 
+/// Formats a duration as days, hours, minutes and seconds
+fn format_uptime(uptime: Duration) -> String {
+    let secs = uptime.as_secs();
+
+    format!(
+        "{}d {}h {}m {}s",
+        secs / 86400,
+        (secs % 86400) / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
 #[poise::command(category = "Stats", prefix_command, slash_command, user_cooldown = 1)]
 pub async fn stats(ctx: Context<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+
+    let configured_guilds = sqlx::query!("SELECT COUNT(*) FROM guilds")
+        .fetch_one(&data.pool)
+        .await?
+        .count
+        .unwrap_or_default();
+
+    let pool_size = data.pool.size();
+    let pool_idle = data.pool.num_idle();
+    let pool_max = data.pool.options().get_max_connections();
+
+    let mod_action_latency = match data.metrics.mod_action_latency.average() {
+        Some(latency) => format!("{:.1}ms", latency.as_secs_f64() * 1000.0),
+        None => "No actions yet".to_string(),
+    };
+
     let msg = CreateReply::default().embed(
         CreateEmbed::default()
             .title("Bot Stats")
@@ -24,12 +59,199 @@ pub async fn stats(ctx: Context<'_>) -> Result<(), Error> {
                 GIT_SHA.to_string() + "(semver=" + GIT_SEMVER + ")",
                 true,
             )
-            //.field("Uptime", format!("{}", chrono::Duration::from_std(std::time::SystemTime::now().duration_since(start_time)).unwrap()), true)
+            .field("Uptime", format_uptime(data.metrics.uptime()), true)
             .field("Commit Message", GIT_COMMIT_MSG, true)
             .field("Built On", BUILD_CPU, true)
-            .field("Cargo Profile", CARGO_PROFILE, true),
+            .field("Cargo Profile", CARGO_PROFILE, true)
+            .field("Guilds", ctx.cache().guild_count().to_string(), true)
+            .field("Configured Guilds", configured_guilds.to_string(), true)
+            .field(
+                "Audit Events",
+                format!(
+                    "{} total, {:.1}/min",
+                    data.metrics.audit_events.total(),
+                    data.metrics.audit_event_rate.per_minute()
+                ),
+                true,
+            )
+            .field("Avg. Action Handling", mod_action_latency, true)
+            .field(
+                "DB Pool",
+                format!(
+                    "{} of {} in use ({} open)",
+                    (pool_size as usize).saturating_sub(pool_idle),
+                    pool_max,
+                    pool_size
+                ),
+                true,
+            ),
     );
 
     ctx.send(msg).await?;
     Ok(())
 }
+
+/// Activity statistics of this server
+#[poise::command(
+    category = "Stats",
+    prefix_command,
+    slash_command,
+    guild_only,
+    user_cooldown = 5
+)]
+pub async fn guildstats(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Could not get guild id")?;
+
+    let by_type = sqlx::query!(
+        "
+            SELECT limit_type,
+            COUNT(*) FILTER (WHERE created_at > NOW() - INTERVAL '1 day') AS \"day!\",
+            COUNT(*) FILTER (WHERE created_at > NOW() - INTERVAL '7 days') AS \"week!\",
+            COUNT(*) AS \"month!\"
+            FROM user_actions
+            WHERE guild_id = $1
+            AND created_at > NOW() - INTERVAL '30 days'
+            GROUP BY limit_type
+            ORDER BY 4 DESC
+        ",
+        guild_id.to_string()
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    let hits = sqlx::query!(
+        "
+            SELECT COUNT(*) FILTER (WHERE created_at > NOW() - INTERVAL '7 days') AS \"week!\",
+            COUNT(*) AS \"total!\",
+            COUNT(*) FILTER (WHERE punishment_failed) AS \"failed!\"
+            FROM past_hit_limits
+            WHERE guild_id = $1
+        ",
+        guild_id.to_string()
+    )
+    .fetch_one(&ctx.data().pool)
+    .await?;
+
+    let moderators = sqlx::query!(
+        "
+            SELECT user_id, COUNT(*) AS \"count!\"
+            FROM user_actions
+            WHERE guild_id = $1
+            AND created_at > NOW() - INTERVAL '30 days'
+            GROUP BY user_id
+            ORDER BY 2 DESC
+            LIMIT 5
+        ",
+        guild_id.to_string()
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    // One row per day of the last week, including days without any activity
+    let daily = sqlx::query!(
+        "
+            SELECT d.day AS \"day!\",
+            (
+                SELECT COUNT(*) FROM user_actions
+                WHERE guild_id = $1
+                AND created_at >= d.day
+                AND created_at < d.day + INTERVAL '1 day'
+            ) AS \"actions!\",
+            (
+                SELECT COUNT(*) FROM past_hit_limits
+                WHERE guild_id = $1
+                AND created_at >= d.day
+                AND created_at < d.day + INTERVAL '1 day'
+            ) AS \"hits!\"
+            FROM generate_series(
+                date_trunc('day', NOW()) - INTERVAL '6 days',
+                date_trunc('day', NOW()),
+                INTERVAL '1 day'
+            ) AS d(day)
+            ORDER BY d.day
+        ",
+        guild_id.to_string()
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    let mut actions = by_type
+        .iter()
+        .map(|r| {
+            let name = r
+                .limit_type
+                .parse::<crate::core::UserLimitTypes>()
+                .map(|t| t.to_cond())
+                .unwrap_or_else(|_| r.limit_type.clone());
+
+            format!("{:<28}{:>6}{:>6}{:>6}", name, r.day, r.week, r.month)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    actions = if actions.is_empty() {
+        "No actions in the last 30 days".to_string()
+    } else {
+        format!(
+            "```\n{:<28}{:>6}{:>6}{:>6}\n{}\n```",
+            "", "24h", "7d", "30d", actions
+        )
+    };
+
+    let mut top = moderators
+        .iter()
+        .enumerate()
+        .map(|(i, r)| format!("{}. <@{}>: {} actions", i + 1, r.user_id, r.count))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if top.is_empty() {
+        top = "None".to_string();
+    }
+
+    let chart = crate::chart::bar_chart(&[
+        crate::chart::Series {
+            values: &daily.iter().map(|d| d.actions).collect::<Vec<_>>(),
+            color: [0x57, 0xf2, 0x87],
+        },
+        crate::chart::Series {
+            values: &daily.iter().map(|d| d.hits).collect::<Vec<_>>(),
+            color: [0xed, 0x42, 0x45],
+        },
+    ])?;
+
+    let days = daily
+        .iter()
+        .map(|d| d.day.format("%a").to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let msg = CreateReply::default()
+        .embed(
+            CreateEmbed::default()
+                .title("Server Stats")
+                .field("Actions Recorded", actions, false)
+                .field("Limits Hit (7d)", hits.week.to_string(), true)
+                .field("Limits Hit (Total)", hits.total.to_string(), true)
+                .field(
+                    "Punishments",
+                    format!(
+                        "{} succeeded, {} failed",
+                        hits.total - hits.failed,
+                        hits.failed
+                    ),
+                    true,
+                )
+                .field("Most Active (30d)", top, false)
+                .image("attachment://activity.png")
+                .footer(CreateEmbedFooter::new(format!(
+                    "Actions (green) and limit hits (red) per day: {}",
+                    days
+                )))
+                .color(0x00ff00),
+        )
+        .attachment(CreateAttachment::bytes(chart, "activity.png"));
+
+    ctx.send(msg).await?;
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use log::error;
+use poise::serenity_prelude::GuildId;
+
+use crate::health::HealthReport;
+
+pub async fn health_check(ctx: &serenity::all::Context) -> Result<(), crate::Error> {
+    let data = ctx.data::<crate::Data>();
+
+    let guilds = sqlx::query!("SELECT guild_id FROM guilds")
+        .fetch_all(&data.pool)
+        .await?;
+
+    let bot_id = ctx.cache.current_user().id;
+
+    for guild in guilds {
+        let guild_id = match guild.guild_id.parse::<GuildId>() {
+            Ok(guild_id) => guild_id,
+            Err(e) => {
+                error!("Invalid guild id {}: {}", guild.guild_id, e);
+                continue;
+            }
+        };
+
+        let limits = match crate::core::Limit::from_guild(&data.pool, guild_id).await {
+            Ok(limits) => limits,
+            Err(e) => {
+                error!("Failed to fetch limits of guild {}: {}", guild_id, e);
+                continue;
+            }
+        };
+
+        let report = {
+            let Some(guild) = ctx.cache.guild(guild_id) else {
+                continue;
+            };
+
+            match HealthReport::check(&guild, bot_id, &limits) {
+                Ok(report) => report,
+                Err(e) => {
+                    error!("Failed to check health of guild {}: {}", guild_id, e);
+                    continue;
+                }
+            }
+        };
+
+        // Only bother the log channel when something is wrong
+        if report.is_healthy() {
+            continue;
+        }
+
+        if let Err(e) = crate::utils::send_log(ctx, &data.pool, guild_id, report.to_embed()).await {
+            error!("Failed to send health check to guild {}: {}", guild_id, e);
+        }
+    }
+
+    Ok(())
+}
@@ -1,8 +1,26 @@
+pub mod health_check;
+pub mod prune_spam_tracker;
 pub mod update_status;
 
+use std::future::Future;
+
 use botox::taskman::Task;
 use futures_util::FutureExt;
 
+/// Runs a task and records that it ran, which the health checks use to tell that the
+/// task manager is still alive
+async fn tracked(
+    name: &str,
+    ctx: &serenity::all::Context,
+    task: impl Future<Output = Result<(), crate::Error>>,
+) -> Result<(), crate::Error> {
+    let res = task.await;
+
+    ctx.data::<crate::Data>().metrics.task_ran(name);
+
+    res
+}
+
 pub fn tasks() -> Vec<Task> {
     vec![
         Task {
@@ -11,7 +29,25 @@ pub fn tasks() -> Vec<Task> {
             enabled: true,
             duration: std::time::Duration::from_secs(600),
             run: Box::new(move |ctx| {
-                update_status::update_status(ctx).boxed()
+                tracked("Update Status", ctx, update_status::update_status(ctx)).boxed()
+            })
+        },
+        Task {
+            name: "Health Check",
+            description: "Warn guilds about moderators and limits Skynet cannot act on",
+            enabled: true,
+            duration: std::time::Duration::from_secs(60 * 60 * 12),
+            run: Box::new(move |ctx| {
+                tracked("Health Check", ctx, health_check::health_check(ctx)).boxed()
+            })
+        },
+        Task {
+            name: "Prune Spam Tracker",
+            description: "Forget messages that are too old for spam detection",
+            enabled: true,
+            duration: std::time::Duration::from_secs(300),
+            run: Box::new(move |ctx| {
+                tracked("Prune Spam Tracker", ctx, prune_spam_tracker::prune_spam_tracker(ctx)).boxed()
             })
         }
     ]
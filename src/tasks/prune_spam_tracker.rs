@@ -0,0 +1,7 @@
+pub async fn prune_spam_tracker(ctx: &serenity::all::Context) -> Result<(), crate::Error> {
+    let data = ctx.data::<crate::Data>();
+
+    data.spam_tracker.prune();
+
+    Ok(())
+}
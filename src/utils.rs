@@ -1,15 +1,166 @@
-use poise::serenity_prelude::GuildId;
+use poise::serenity_prelude::{
+    ButtonStyle, ChannelId, ComponentInteraction, ComponentInteractionCollector, CreateActionRow,
+    CreateButton, CreateEmbed, CreateInteractionResponse, CreateMessage, GuildId, MessageId,
+    RoleId, UserId,
+};
+use serenity::model::guild::audit_log::Change;
 use sqlx::postgres::types::PgInterval;
+use sqlx::types::chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+pub fn pg_interval_to_secs(i: &PgInterval) -> i64 {
+    i.microseconds / 1000000 + ((i.days * 86400) as i64) + ((i.months * 2628000) as i64)
+}
 
 pub fn parse_pg_interval(i: PgInterval) -> String {
-    let seconds =
-        i.microseconds / 1000000 + ((i.days * 86400) as i64) + ((i.months * 2628000) as i64);
+    let seconds = pg_interval_to_secs(&i);
 
     let dur = std::time::Duration::from_secs(seconds.try_into().unwrap_or_default());
 
     format!("{:?}", dur)
 }
 
+/// Formats a limit target as a channel or role mention, falling back to the raw ID
+pub fn format_target(guild: &serenity::all::Guild, target: &str) -> String {
+    let Ok(id) = target.parse::<u64>() else {
+        return format!("``{}``", target);
+    };
+
+    if guild.channels.get(&ChannelId::new(id)).is_some() {
+        format!("<#{}>", id)
+    } else if guild.roles.get(&RoleId::new(id)).is_some() {
+        format!("<@&{}>", id)
+    } else {
+        format!("``{}``", id)
+    }
+}
+
+/// Posts an embed to the log channel of a guild, if one is set
+pub async fn send_log(
+    ctx: &serenity::all::Context,
+    pool: &sqlx::PgPool,
+    guild_id: GuildId,
+    embed: CreateEmbed<'_>,
+) -> Result<(), crate::Error> {
+    let rec = sqlx::query!(
+        "SELECT log_channel FROM guilds WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(log_channel) = rec.and_then(|r| r.log_channel) else {
+        return Ok(());
+    };
+
+    log_channel
+        .parse::<ChannelId>()?
+        .send_message(ctx, CreateMessage::new().embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+/// Returns true if Skynet itself has the role
+pub fn is_bot_role(cache: &serenity::all::Cache, guild_id: GuildId, role_id: RoleId) -> bool {
+    let bot_id = cache.current_user().id;
+
+    let Some(guild) = cache.guild(guild_id) else {
+        return false;
+    };
+
+    guild
+        .members
+        .get(&bot_id)
+        .map(|m| m.roles.contains(&role_id))
+        .unwrap_or_default()
+}
+
+/// Returns the position of the highest role of Skynet in a guild
+pub fn bot_top_position(cache: &serenity::all::Cache, guild_id: GuildId) -> Option<u32> {
+    let bot_id = cache.current_user().id;
+    let guild = cache.guild(guild_id)?;
+    let member = guild.members.get(&bot_id)?;
+
+    member
+        .roles
+        .iter()
+        .filter_map(|r| guild.roles.get(r))
+        .map(|r| u32::from(r.position))
+        .max()
+}
+
+/// Returns true if an update to a role of Skynet removed permissions from it or lowered it
+pub fn weakens_bot_role<'a>(changes: impl IntoIterator<Item = &'a Change>) -> bool {
+    changes.into_iter().any(|change| match change {
+        Change::Permissions {
+            old: Some(old),
+            new: Some(new),
+        } => !new.contains(*old),
+        Change::Position {
+            old: Some(old),
+            new: Some(new),
+        } => u32::from(*new) < u32::from(*old),
+        _ => false,
+    })
+}
+
+/// Returns true if an update to a role moved it above the highest role of Skynet
+pub fn raises_above_bot<'a>(
+    cache: &serenity::all::Cache,
+    guild_id: GuildId,
+    changes: impl IntoIterator<Item = &'a Change>,
+) -> bool {
+    let Some(top) = bot_top_position(cache, guild_id) else {
+        return false;
+    };
+
+    changes.into_iter().any(|change| match change {
+        Change::Position {
+            old,
+            new: Some(new),
+        } => u32::from(*new) > top && old.map_or(true, |old| u32::from(old) < u32::from(*new)),
+        _ => false,
+    })
+}
+
+/// Returns true if a member update removed roles from the member
+pub fn removes_roles<'a>(changes: impl IntoIterator<Item = &'a Change>) -> bool {
+    changes
+        .into_iter()
+        .any(|change| matches!(change, Change::RolesRemove { .. }))
+}
+
+/// Sends an embed to a user by DM
+pub async fn send_dm(
+    ctx: &serenity::all::Context,
+    user_id: UserId,
+    embed: CreateEmbed<'_>,
+) -> Result<(), crate::Error> {
+    user_id
+        .direct_message(ctx, CreateMessage::new().embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+/// Fetch the quarantine role of a guild, if one is set
+pub async fn quarantine_role(
+    pool: &sqlx::PgPool,
+    guild_id: GuildId,
+) -> Result<Option<RoleId>, crate::Error> {
+    let rec = sqlx::query!(
+        "SELECT quarantine_role FROM guilds WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match rec.and_then(|r| r.quarantine_role) {
+        Some(role) => Ok(Some(role.parse()?)),
+        None => Ok(None),
+    }
+}
+
 pub async fn is_guild_admin(
     cache_http: impl serenity::all::CacheHttp,
     pool: &sqlx::PgPool,
@@ -41,11 +192,126 @@ pub async fn is_guild_admin(
     .await
     .map_err(|_| "Could not fetch guild admin status")?;
 
-    if count.count.unwrap_or_default() == 0 {
-        return Err("You are not a guild admin".into());
+    if count.count.unwrap_or_default() > 0 {
+        return Ok(());
     }
 
-    Ok(())
+    // Check if user has any admin role
+    let admin_roles = sqlx::query!(
+        "SELECT role_id FROM guild_admin_roles WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|_| "Could not fetch guild admin roles")?;
+
+    if !admin_roles.is_empty() {
+        let member = guild_id
+            .member(&cache_http, user_id.parse::<UserId>()?)
+            .await
+            .map_err(|_| "Could not fetch member")?;
+
+        if admin_roles
+            .iter()
+            .any(|r| member.roles.iter().any(|m| m.to_string() == r.role_id))
+        {
+            return Ok(());
+        }
+    }
+
+    Err("You are not a guild admin".into())
+}
+
+/// Checks if a user is a guild admin or has been granted the given capability
+pub async fn has_capability(
+    cache_http: impl serenity::all::CacheHttp,
+    pool: &sqlx::PgPool,
+    guild_id: GuildId,
+    user_id: UserId,
+    capability: crate::core::Capability,
+) -> Result<(), crate::Error> {
+    if is_guild_admin(&cache_http, pool, guild_id, user_id.to_string())
+        .await
+        .is_ok()
+    {
+        return Ok(());
+    }
+
+    let grants = sqlx::query!(
+        "SELECT target_id, target_type FROM guild_capabilities WHERE guild_id = $1 AND capability = $2",
+        guild_id.to_string(),
+        capability.to_string()
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|_| "Could not fetch guild capabilities")?;
+
+    let targets = grants
+        .into_iter()
+        .map(|g| (g.target_id, g.target_type))
+        .collect::<Vec<_>>();
+
+    if matches_targets(&cache_http, guild_id, user_id, &targets).await? {
+        return Ok(());
+    }
+
+    Err(format!(
+        "You need to be a guild admin or have the ``{}`` capability to use this command",
+        capability.to_cond()
+    )
+    .into())
+}
+
+/// Checks if a user or any of their roles is on the guild whitelist
+pub async fn is_whitelisted(
+    cache_http: impl serenity::all::CacheHttp,
+    pool: &sqlx::PgPool,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<bool, crate::Error> {
+    let whitelist = sqlx::query!(
+        "SELECT target_id, target_type FROM guild_whitelist WHERE guild_id = $1",
+        guild_id.to_string()
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let targets = whitelist
+        .into_iter()
+        .map(|w| (w.target_id, w.target_type))
+        .collect::<Vec<_>>();
+
+    matches_targets(&cache_http, guild_id, user_id, &targets).await
+}
+
+/// Checks a user against a list of (target_id, target_type) pairs, where target_type is user or role
+async fn matches_targets(
+    cache_http: impl serenity::all::CacheHttp,
+    guild_id: GuildId,
+    user_id: UserId,
+    targets: &[(String, String)],
+) -> Result<bool, crate::Error> {
+    let user_id_str = user_id.to_string();
+
+    if targets
+        .iter()
+        .any(|(id, typ)| typ == "user" && *id == user_id_str)
+    {
+        return Ok(true);
+    }
+
+    if !targets.iter().any(|(_, typ)| typ == "role") {
+        return Ok(false);
+    }
+
+    let member = guild_id
+        .member(&cache_http, user_id)
+        .await
+        .map_err(|_| "Could not fetch member")?;
+
+    Ok(targets.iter().any(|(id, typ)| {
+        typ == "role" && member.roles.iter().any(|r| r.to_string() == *id)
+    }))
 }
 
 #[derive(poise::ChoiceParameter)]
@@ -70,3 +336,60 @@ impl Unit {
         }
     }
 }
+
+/// Waits for the command author to use a component on a message, acknowledging it
+pub async fn next_component(
+    ctx: crate::Context<'_>,
+    msg_id: MessageId,
+    timeout_secs: u64,
+) -> Result<Option<ComponentInteraction>, crate::Error> {
+    let Some(interaction) = ComponentInteractionCollector::new(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .message_id(msg_id)
+        .timeout(std::time::Duration::from_secs(timeout_secs))
+        .await
+    else {
+        return Ok(None);
+    };
+
+    interaction
+        .create_response(ctx.http(), CreateInteractionResponse::Acknowledge)
+        .await?;
+
+    Ok(Some(interaction))
+}
+
+/// Previous and next buttons for a paginated reply, pages are zero-indexed
+pub fn page_buttons(page: i64, pages: i64) -> CreateActionRow<'static> {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new("page_prev")
+            .label("Previous")
+            .style(ButtonStyle::Secondary)
+            .disabled(page <= 0),
+        CreateButton::new("page_indicator")
+            .label(format!("{}/{}", page + 1, pages.max(1)))
+            .style(ButtonStyle::Secondary)
+            .disabled(true),
+        CreateButton::new("page_next")
+            .label("Next")
+            .style(ButtonStyle::Secondary)
+            .disabled(page + 1 >= pages),
+    ])
+}
+
+/// Moves a page index according to a pressed page button
+pub fn turn_page(custom_id: &str, page: i64, pages: i64) -> i64 {
+    match custom_id {
+        "page_prev" => (page - 1).max(0),
+        "page_next" => (page + 1).min(pages - 1).max(0),
+        _ => page,
+    }
+}
+
+/// Parses a YYYY-MM-DD date as midnight UTC
+pub fn parse_date(date: &str) -> Result<DateTime<Utc>, crate::Error> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date ``{}``, use YYYY-MM-DD", date))?;
+
+    Ok(date.and_time(NaiveTime::MIN).and_utc())
+}
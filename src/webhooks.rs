@@ -0,0 +1,241 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use hmac::{Hmac, Mac};
+use log::{error, info};
+use poise::serenity_prelude::GuildId;
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::PgPool;
+use strum_macros::Display;
+
+use crate::Error;
+
+/// Times a delivery is attempted before giving up
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Serialize, Display, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum WebhookEvent {
+    /// A limit was hit, data is a `PastHitLimits`
+    LimitHit,
+    /// The punishment for a hit limit was applied, data is a `PastHitLimits`
+    PunishmentSucceeded,
+    /// The punishment for a hit limit could not be applied, data is a `PastHitLimits`
+    PunishmentFailed,
+    /// Sent by `/webhooks test`, data is null
+    Test,
+    /// Skynet was removed from the guild, data is `{ "guild_name" }`
+    BotRemoved,
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a, T: Serialize> {
+    event: WebhookEvent,
+    guild_id: GuildId,
+    data: &'a T,
+}
+
+/// Returns true if an address is reachable from the internet, so webhooks cannot be used to
+/// reach Skynet itself, the local network or cloud metadata services
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // Shared address space (100.64.0.0/10)
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+
+            let first = ip.segments()[0];
+
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local (fc00::/7) and link local (fe80::/10)
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Checks that a webhook URL uses https and that its host only resolves to public addresses,
+/// returning the address to connect to so the host cannot be pointed elsewhere afterwards
+pub async fn resolve_url(url: &str) -> Result<(reqwest::Url, SocketAddr), Error> {
+    let parsed = reqwest::Url::parse(url).map_err(|_| "Invalid URL")?;
+
+    if parsed.scheme() != "https" {
+        return Err("Webhook URLs must use https".into());
+    }
+
+    let host = parsed.host_str().ok_or("Webhook URLs must have a host")?;
+    let port = parsed.port_or_known_default().unwrap_or(443);
+
+    let addrs = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_| format!("Could not resolve ``{}``", host))?
+        .collect::<Vec<_>>();
+
+    if addrs.is_empty() {
+        return Err(format!("Could not resolve ``{}``", host).into());
+    }
+
+    if addrs.iter().any(|a| !is_public(a.ip())) {
+        return Err("Webhook URLs must not point to private or local addresses".into());
+    }
+
+    Ok((parsed, addrs[0]))
+}
+
+/// Returns the host of a webhook URL, as the rest of the URL may contain a token
+pub fn url_host(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+        .unwrap_or_default()
+}
+
+pub struct Webhook {
+    pub webhook_id: String,
+    pub url: String,
+    pub secret: String,
+}
+
+impl Webhook {
+    pub async fn from_guild(pool: &PgPool, guild_id: GuildId) -> Result<Vec<Self>, Error> {
+        let rec = sqlx::query!(
+            "
+                SELECT webhook_id, url, secret
+                FROM guild_webhooks
+                WHERE guild_id = $1
+            ",
+            guild_id.to_string()
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rec
+            .into_iter()
+            .map(|r| Self {
+                webhook_id: r.webhook_id,
+                url: r.url,
+                secret: r.secret,
+            })
+            .collect())
+    }
+
+    /// Posts a signed body to the webhook, retrying with backoff on failure
+    ///
+    /// The hex encoded HMAC-SHA256 of the body is sent in the `X-Skynet-Signature` header
+    pub async fn deliver(&self, event: WebhookEvent, body: &[u8]) -> Result<(), Error> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .map_err(|_| "Invalid webhook secret")?;
+        mac.update(body);
+
+        let signature = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
+        // Checked on every delivery as the host may resolve differently than when it was added
+        let (url, addr) = resolve_url(&self.url).await?;
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::none())
+            .resolve(url.host_str().unwrap_or_default(), addr)
+            .build()?;
+
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            let res = client
+                .post(url.clone())
+                .header("Content-Type", "application/json")
+                .header("X-Skynet-Event", event.to_string())
+                .header("X-Skynet-Signature", format!("sha256={}", signature))
+                .body(body.to_vec())
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+
+            match res {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    info!(
+                        "Webhook {} delivery attempt {} failed: {}",
+                        self.webhook_id, attempt, e
+                    );
+
+                    tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Serializes an event and delivers it to every webhook of the guild in the background
+pub async fn fire<T: Serialize>(pool: &PgPool, guild_id: GuildId, event: WebhookEvent, data: &T) {
+    let webhooks = match Webhook::from_guild(pool, guild_id).await {
+        Ok(webhooks) => webhooks,
+        Err(e) => {
+            error!("Failed to fetch webhooks of guild {}: {}", guild_id, e);
+            return;
+        }
+    };
+
+    if webhooks.is_empty() {
+        return;
+    }
+
+    let body = match serde_json::to_vec(&WebhookPayload {
+        event,
+        guild_id,
+        data,
+    }) {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Failed to serialize webhook payload: {}", e);
+            return;
+        }
+    };
+
+    for webhook in webhooks {
+        let body = body.clone();
+
+        tokio::task::spawn(async move {
+            if let Err(e) = webhook.deliver(event, &body).await {
+                error!("Failed to deliver webhook {}: {}", webhook.webhook_id, e);
+            }
+        });
+    }
+}
+
+/// Builds the body of a test event
+pub fn test_body(guild_id: GuildId) -> Result<Vec<u8>, Error> {
+    Ok(serde_json::to_vec(&WebhookPayload {
+        event: WebhookEvent::Test,
+        guild_id,
+        data: &(),
+    })?)
+}